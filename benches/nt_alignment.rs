@@ -1,20 +1,69 @@
 #[macro_use]
 extern crate criterion;
 
-use seqognize::aligner::Aligner;
-use seqognize::nt_aligner::{GlobalNtAligner, NtAlignmentConfig};
+use seqognize::aligner::{Aligner, AlignerWorkspace};
+use seqognize::edit_distance::{Mode, MyersAligner};
+use seqognize::nt_aligner::{GlobalNtAligner, LocalNtAligner, NtAlignmentConfig};
 use criterion::Criterion;
 
 fn nt_alignment_benchmark(c: &mut Criterion) {
-    let aligner: GlobalNtAligner = GlobalNtAligner {
-        config: NtAlignmentConfig {
+    let aligner = GlobalNtAligner::from(
+        NtAlignmentConfig {
             match_score: 1.0,
             mismatch_penalty: -1.0,
             subject_gap_penalty: -1.0,
+            subject_gap_extension_penalty: -1.0,
             reference_gap_penalty: -1.0,
+            reference_gap_extension_penalty: -1.0,
         }
-    };
+    );
     c.bench_function("NT alignment", |b| b.iter(|| aligner.align(b"ACGTACT", b"ACTACGT")));
+    c.bench_function("NT score", |b| b.iter(|| aligner.score(b"ACGTACT", b"ACTACGT")));
+
+    let reads: Vec<Vec<u8>> = (0..1000).map(|i| b"ACGTACTTGACCATGCAGGTCA"[i % 7..10 + i % 13].to_vec()).collect();
+    c.bench_function("NT alignment 1000 reads", |b| b.iter(||
+        reads.iter().map(|read| aligner.align(read, b"ACTACGTTGACATGGCA").unwrap().score).sum::<f64>()
+    ));
+    c.bench_function("NT alignment 1000 reads with workspace", |b| b.iter(|| {
+        let mut workspace = AlignerWorkspace::new();
+        reads.iter().map(|read| aligner.align_with(&mut workspace, read, b"ACTACGTTGACATGGCA").unwrap().score).sum::<f64>()
+    }));
+
+    let subject = b"ACGTACTTGACCATGCA".repeat(30);
+    let reference = b"ACTACGTTGACATGGCA".repeat(30);
+    c.bench_function("NT alignment 500bp", |b| b.iter(|| aligner.align(&subject, &reference)));
+    c.bench_function("NT score 500bp", |b| b.iter(|| aligner.score(&subject, &reference)));
+    c.bench_function("NT wavefront alignment 500bp", |b| b.iter(|| aligner.align_wavefront(&subject, &reference)));
+    c.bench_function("NT striped score 500bp", |b| b.iter(|| aligner.striped_score(&subject, &reference)));
+    let local_aligner = LocalNtAligner::from(
+        NtAlignmentConfig {
+            match_score: 1.0,
+            mismatch_penalty: -1.0,
+            subject_gap_penalty: -1.0,
+            subject_gap_extension_penalty: -1.0,
+            reference_gap_penalty: -1.0,
+            reference_gap_extension_penalty: -1.0,
+        }
+    );
+    c.bench_function("NT local score 500bp", |b| b.iter(|| local_aligner.score(&subject, &reference)));
+    c.bench_function("NT local striped score 500bp", |b| b.iter(|| local_aligner.striped_score(&subject, &reference)));
+
+    let integer_aligner = GlobalNtAligner::from(
+        NtAlignmentConfig {
+            match_score: 1,
+            mismatch_penalty: -1,
+            subject_gap_penalty: -1,
+            subject_gap_extension_penalty: -1,
+            reference_gap_penalty: -1,
+            reference_gap_extension_penalty: -1,
+        }
+    );
+    c.bench_function("NT alignment 500bp i32", |b| b.iter(|| integer_aligner.align(&subject, &reference)));
+    c.bench_function("NT score 500bp i32", |b| b.iter(|| integer_aligner.score(&subject, &reference)));
+
+    let myers_aligner = MyersAligner { mode: Mode::Global };
+    c.bench_function("NT edit distance 500bp", |b| b.iter(|| myers_aligner.distance(&subject, &reference)));
+    c.bench_function("NT edit alignment 500bp", |b| b.iter(|| myers_aligner.align(&subject, &reference)));
 }
 
 criterion_group!(nt_alignment, nt_alignment_benchmark);
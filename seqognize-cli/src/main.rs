@@ -1,11 +1,32 @@
 #![allow(dead_code)]
 
+use seqognize::aligner::{Aligner, AlignerWorkspace, GlobalAligner};
 use seqognize::alignment::Alignment;
+use seqognize::iupac::reverse_complement;
 use seqognize::nt_aligner::{GlobalNtAligner, NtAlignmentConfig};
-use seqognize::aligner::Aligner;
+use seqognize::quality::QualityConfig;
+use seqognize::strand::{Strand, StrandedAlignment};
+use seqognize::batch::BatchAligner;
+use seqognize::sam::SamWriter;
+use seqognize::fastx::{Record, Records};
+use seqognize::Error;
 use clap::{App, Arg, ArgMatches};
+use flate2::bufread::MultiGzDecoder;
 use std::str::FromStr;
-use std::fmt::Debug;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::process;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+const EXIT_ARGUMENT: i32 = 2;
+const EXIT_IO: i32 = 3;
+const EXIT_INPUT: i32 = 4;
+const EXIT_CONFIG: i32 = 5;
+
+type RecordIter = Box<dyn Iterator<Item=io::Result<Record>>>;
 
 fn main() {
     let matches = App::new("Seqognize")
@@ -15,13 +36,13 @@ fn main() {
         .arg(Arg::with_name("reference")
             .short("r")
             .long("ref")
-            .help("Reference sequence")
+            .help("Reference sequence, FASTA/FASTQ file (optionally gzipped) or - for stdin")
             .required(true)
             .takes_value(true))
         .arg(Arg::with_name("subject")
             .short("s")
             .long("sub")
-            .help("Subject sequence")
+            .help("Subject sequence, FASTA/FASTQ file (optionally gzipped) or - for stdin")
             .required(true)
             .takes_value(true))
         .arg(Arg::with_name("match")
@@ -44,34 +65,205 @@ fn main() {
             .long("rg")
             .help("Reference gap opening")
             .takes_value(true))
+        .arg(Arg::with_name("subject_gap_extension")
+            .long("sge")
+            .help("Subject gap extension (defaults to subject gap opening)")
+            .takes_value(true))
+        .arg(Arg::with_name("reference_gap_extension")
+            .long("rge")
+            .help("Reference gap extension (defaults to reference gap opening)")
+            .takes_value(true))
         .arg(Arg::with_name("vertical")
             .long("vertical")
             .help("Vertical output")
             .takes_value(false))
+        .arg(Arg::with_name("format")
+            .long("format")
+            .help("Output format")
+            .possible_values(&["text", "sam"])
+            .default_value("text")
+            .takes_value(true))
+        .arg(Arg::with_name("quality")
+            .short("q")
+            .long("quality")
+            .help("Weight the scores of FASTQ subjects by their base qualities")
+            .takes_value(false))
+        .arg(Arg::with_name("both_strands")
+            .short("b")
+            .long("both-strands")
+            .help("Also align the reverse complement of each subject, keeping the better alignment")
+            .takes_value(false))
+        .arg(Arg::with_name("threads")
+            .short("t")
+            .long("threads")
+            .help("Alignment threads, 0 for one per core")
+            .default_value("1")
+            .takes_value(true))
         .get_matches();
 
-    let reference = matches.value_of("reference").unwrap().as_bytes();
-    let subject = matches.value_of("subject").unwrap().as_bytes();
+    let reference_arg = matches.value_of("reference").unwrap();
+    let subject_arg = matches.value_of("subject").unwrap();
+    if reference_arg == "-" && subject_arg == "-" {
+        fail(EXIT_ARGUMENT, "only one of --ref and --sub can be read from stdin");
+    }
+    let reference = records(reference_arg, "reference")
+        .and_then(|mut records| records.next().transpose())
+        .unwrap_or_else(|e| fail(io_exit_code(&e), &format!("cannot read reference: {}", e)))
+        .unwrap_or_else(|| fail(EXIT_INPUT, "no reference sequence"));
+    let subjects = records(subject_arg, "subject")
+        .unwrap_or_else(|e| fail(io_exit_code(&e), &format!("cannot read subject: {}", e)));
+    let named = !is_literal(reference_arg) || !is_literal(subject_arg);
 
-    let aligner: GlobalNtAligner = GlobalNtAligner {
-        config: NtAlignmentConfig {
+    let subject_gap_penalty = arg(&matches, "subject_gap", -1.0);
+    let reference_gap_penalty = arg(&matches, "reference_gap", -1.0);
+    let threads = arg(&matches, "threads", 1);
+    let aligner = GlobalNtAligner::from(
+        NtAlignmentConfig {
             match_score: arg(&matches, "match", 1.0),
             mismatch_penalty: arg(&matches, "mismatch", -1.0),
-            subject_gap_penalty: arg(&matches, "subject_gap", -1.0),
-            reference_gap_penalty: arg(&matches, "reference_gap", -1.0),
+            subject_gap_penalty,
+            subject_gap_extension_penalty: arg(&matches, "subject_gap_extension", subject_gap_penalty),
+            reference_gap_penalty,
+            reference_gap_extension_penalty: arg(&matches, "reference_gap_extension", reference_gap_penalty),
         }
+    );
+    let batch = BatchAligner::new(aligner, threads)
+        .unwrap_or_else(|e| fail(EXIT_ARGUMENT, &format!("cannot start {} threads: {}", threads, e)));
+
+    let mut sam = if matches.value_of("format") == Some("sam") {
+        Some(SamWriter::new(io::stdout(), &reference.name, reference.sequence.len())
+            .unwrap_or_else(|e| fail(EXIT_IO, &e.to_string())))
+    } else {
+        None
+    };
+    let quality_aware = matches.is_present("quality");
+    // Subjects that cannot be read fail in the output loop, after the alignments before them.
+    let subjects = subjects.map(|subject| {
+        let subject = subject.map_err(|e| (io_exit_code(&e), format!("cannot read subject: {}", e)))?;
+        if quality_aware && subject.quality.is_none() {
+            let message = format!("--quality needs FASTQ subjects, but {} has no qualities", subject.name);
+            return Err((EXIT_INPUT, message));
+        }
+        Ok(subject)
+    });
+    let both_strands = matches.is_present("both_strands");
+    let reference_sequence = reference.sequence.as_slice();
+    let alignments = batch.align_by(subjects, |aligner, workspace, subject| {
+        subject.as_ref().ok().map(|subject| -> seqognize::Result<StrandedAlignment> {
+            let quality = subject.quality.as_deref().filter(|_| quality_aware);
+            let forward = align_read(aligner, workspace, &subject.sequence, quality, reference_sequence)?;
+            if !both_strands {
+                return Ok(StrandedAlignment { alignment: forward, strand: Strand::Forward });
+            }
+            let reversed_quality: Option<Vec<u8>> = quality.map(|q| q.iter().rev().copied().collect());
+            let reverse = align_read(aligner, workspace, &reverse_complement(&subject.sequence), reversed_quality.as_deref(), reference_sequence)?;
+            Ok(StrandedAlignment::better(forward, reverse))
+        })
+    });
+    for (subject, stranded) in alignments {
+        let subject = subject.unwrap_or_else(|(code, message)| fail(code, &message));
+        let stranded = stranded
+            .expect("every subject read is aligned")
+            .unwrap_or_else(|e| fail(exit_code(&e), &format!("cannot align {}: {}", subject.name, e)));
+        if let Some(writer) = sam.as_mut() {
+            writer.write_stranded(&subject.name, &subject.sequence, subject.quality.as_deref(), &stranded)
+                .unwrap_or_else(|e| fail(EXIT_IO, &e.to_string()));
+            continue;
+        }
+        if named {
+            println!("{} vs {}", subject.name, reference.name);
+        }
+        let alignment = stranded.alignment;
+        println!("Score: {:?}", alignment.score);
+        if both_strands {
+            println!("Strand: {}", stranded.strand);
+        }
+        println!("{}", alignment.stats(subject.sequence.len(), reference.sequence.len()));
+        if matches.is_present("vertical") {
+            alignment.print_vertical();
+        } else {
+            alignment.print_horizontal();
+        }
+    }
+}
+
+/// Aligns a read, weighting its scores by its qualities when given.
+fn align_read(
+    aligner: &GlobalNtAligner,
+    workspace: &mut AlignerWorkspace,
+    read: &[u8],
+    quality: Option<&[u8]>,
+    reference: &[u8],
+) -> seqognize::Result<Alignment> {
+    match quality {
+        Some(quality) => GlobalAligner::from(QualityConfig::new(&aligner.config, quality)).align_with(workspace, read, reference),
+        None => aligner.align_with(workspace, read, reference)
+    }
+}
+
+/// The records of a FASTA/FASTQ file or of stdin, or a single record holding `value` itself.
+fn records(value: &str, literal_name: &str) -> io::Result<RecordIter> {
+    if is_literal(value) {
+        let record = Record { name: literal_name.to_string(), sequence: value.as_bytes().to_vec(), quality: None };
+        return Ok(Box::new(std::iter::once(Ok(record))));
+    }
+    let reader: Box<dyn BufRead> = if value == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(value)?))
     };
+    Ok(Box::new(Records::new(decompressed(reader)?)))
+}
 
-    let alignment = aligner.align(&subject, &reference);
-    println!("Score: {:?}", alignment.score);
-    if matches.is_present("vertical") {
-        alignment.print_vertical();
+fn decompressed(mut reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    if reader.fill_buf()?.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
     } else {
-        alignment.print_horizontal();
+        Ok(reader)
+    }
+}
+
+/// Values that look like paths, holding a path separator or a `.`, are never sequences, so
+/// that a mistyped file name fails as a missing file.
+fn is_literal(value: &str) -> bool {
+    value != "-" &&
+        !value.contains(|c: char| c == '.' || c == '/' || std::path::is_separator(c)) &&
+        !Path::new(value).is_file()
+}
+
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::InvalidConfig { .. } => EXIT_CONFIG,
+        _ => EXIT_INPUT
+    }
+}
+
+/// Malformed FASTA/FASTQ is bad input rather than a failure to read it.
+fn io_exit_code(error: &io::Error) -> i32 {
+    if error.kind() == io::ErrorKind::InvalidData { EXIT_INPUT } else { EXIT_IO }
+}
+
+fn fail(code: i32, message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(code)
+}
+
+fn arg<T: FromStr>(matches: &ArgMatches, argname: &str, default: T) -> T
+    where <T as std::str::FromStr>::Err: Display {
+    match matches.value_of(argname) {
+        Some(value) => value.parse().unwrap_or_else(|e| {
+            fail(EXIT_ARGUMENT, &format!("invalid value {:?} for --{}: {}", value, long_name(argname), e))
+        }),
+        None => default
     }
 }
 
-fn arg<T: FromStr + Debug>(matches: &ArgMatches, argname: &str, default: T) -> T
-    where <T as std::str::FromStr>::Err: std::fmt::Debug {
-    matches.value_of(argname).map(|s| s.parse().unwrap()).unwrap_or(default)
+fn long_name(argname: &str) -> &str {
+    match argname {
+        "subject_gap" => "sg",
+        "reference_gap" => "rg",
+        "subject_gap_extension" => "sge",
+        "reference_gap_extension" => "rge",
+        _ => argname
+    }
 }
\ No newline at end of file
@@ -5,6 +5,7 @@ use yew::prelude::*;
 use yew::services::{DialogService};
 use seqognize::nt_aligner::{GlobalNtAligner, NtAlignmentConfig};
 use seqognize::aligner::Aligner;
+use seqognize::strand::{BothStrands, Strand, StrandedAlignment};
 use std::num::ParseFloatError;
 
 struct Model {
@@ -12,8 +13,10 @@ struct Model {
     subject: String,
     match_score: String,
     mismatch_score: String,
+    both_strands: bool,
     alignment: String,
     score: String,
+    stats: String,
     parser: Parser,
 }
 
@@ -22,6 +25,7 @@ enum Msg {
     SetSubject(String),
     SetMatchScore(String),
     SetMismatchScore(String),
+    ToggleBothStrands,
     Align,
 }
 
@@ -35,8 +39,10 @@ impl Component for Model {
             subject: "".to_string(),
             match_score: "1".to_string(),
             mismatch_score: "-1".to_string(),
+            both_strands: false,
             alignment: "".to_string(),
             score: "".to_string(),
+            stats: "".to_string(),
             parser: Parser::new(),
         }
     }
@@ -50,6 +56,7 @@ impl Component for Model {
                     Some(s) => s.to_string(),
                     _ => "".to_string()
                 };
+                self.stats = result.stats;
             }
             Msg::SetSubject(value) => {
                 self.reference = value;
@@ -67,6 +74,10 @@ impl Component for Model {
                 self.mismatch_score = value;
                 self.reset();
             }
+            Msg::ToggleBothStrands => {
+                self.both_strands = !self.both_strands;
+                self.reset();
+            }
         }
         true
     }
@@ -76,6 +87,7 @@ impl Model {
     fn reset(&mut self) {
         self.alignment = "".to_string();
         self.score = "".to_string();
+        self.stats = "".to_string();
     }
 }
 
@@ -113,6 +125,14 @@ impl Renderable<Model> for Model {
                         />
                     </td>
                 </tr>
+                <tr>
+                    <td>{"Both strands:"}</td>
+                    <td>
+                        <input type="checkbox", checked=self.both_strands,
+                            onclick=|_| Msg::ToggleBothStrands,
+                        />
+                    </td>
+                </tr>
                 <tr>
                     <td align="left", >
                         <button onclick=|_| Msg::Align,>
@@ -131,6 +151,7 @@ impl Renderable<Model> for Model {
                         <input type="numerical", size="5", readonly="true",
                             value={&self.score},
                         />
+                        {&self.stats}
                     </td>
                 </tr>
             </table>
@@ -153,7 +174,7 @@ impl Parser {
             Err(e) => {
                 let msg = format!("Invalid number: {}", value);
                 self.dialog.alert(&msg);
-                return Err(e);
+                Err(e)
             }
         }
     }
@@ -169,35 +190,53 @@ impl Model {
             match_score: self.parser.parse(&self.match_score)?,
             mismatch_penalty: self.parser.parse(&self.mismatch_score)?,
             subject_gap_penalty: -1.0,
+            subject_gap_extension_penalty: -1.0,
             reference_gap_penalty: -1.0,
+            reference_gap_extension_penalty: -1.0,
         })
     }
 
     fn align(&mut self) -> Result<AlignmentResult, ParseFloatError> {
         let config = self.config()?;
-        let aligner = GlobalNtAligner { config };
-        let alignment = aligner.align(
-            &self.subject.as_bytes(),
-            &self.reference.as_bytes(),
-        );
+        let aligner = GlobalNtAligner::from(config);
+        let (subject, reference) = (self.subject.as_bytes(), self.reference.as_bytes());
+        let stranded = if self.both_strands {
+            aligner.align_both_strands(subject, reference)
+        } else {
+            aligner.align(subject, reference).map(|alignment| StrandedAlignment { alignment, strand: Strand::Forward })
+        };
+        let StrandedAlignment { alignment, strand } = match stranded {
+            Ok(stranded) => stranded,
+            Err(e) => {
+                self.parser.dialog.alert(&format!("Cannot align: {}", e));
+                return Ok(AlignmentResult::empty());
+            }
+        };
         let aligned_sequences = alignment.aligned_sequences();
         let alignment_str = format!("{}\n{}\n{}", aligned_sequences.0, aligned_sequences.1, aligned_sequences.2);
-        Ok(AlignmentResult::of(alignment_str, alignment.score))
+        let stats = alignment.stats(self.subject.len(), self.reference.len());
+        let stats = if self.both_strands {
+            format!("Strand: {}, {}", strand, stats)
+        } else {
+            stats.to_string()
+        };
+        Ok(AlignmentResult::of(alignment_str, alignment.score, stats))
     }
 }
 
 struct AlignmentResult {
     alignment: String,
     score: Option<f64>,
+    stats: String,
 }
 
 impl AlignmentResult {
-    fn of(alignment: String, score: f64) -> Self {
-        AlignmentResult { alignment, score: Some(score) }
+    fn of(alignment: String, score: f64, stats: String) -> Self {
+        AlignmentResult { alignment, score: Some(score), stats }
     }
 
     fn empty() -> Self {
-        AlignmentResult { alignment: "".to_string(), score: None }
+        AlignmentResult { alignment: "".to_string(), score: None, stats: "".to_string() }
     }
 }
 
@@ -0,0 +1,126 @@
+use crate::config::AlignmentConfig;
+use crate::aligner::{GlobalAligner, LocalAligner};
+use crate::element::FScore;
+use crate::error::{Result, check_penalty};
+use crate::substitution_matrix::SubstitutionMatrix;
+
+pub struct AaAlignmentConfig {
+    pub matrix: SubstitutionMatrix,
+    pub subject_gap_penalty: FScore,
+    pub subject_gap_extension_penalty: FScore,
+    pub reference_gap_penalty: FScore,
+    pub reference_gap_extension_penalty: FScore,
+}
+
+impl AaAlignmentConfig {
+    /// Symmetric gaps where the first gap position costs `opening` and every further one `extension`.
+    pub fn with_gaps(matrix: SubstitutionMatrix, opening: FScore, extension: FScore) -> Self {
+        AaAlignmentConfig {
+            matrix,
+            subject_gap_penalty: opening,
+            subject_gap_extension_penalty: extension,
+            reference_gap_penalty: opening,
+            reference_gap_extension_penalty: extension,
+        }
+    }
+
+    /// BLASTP defaults: BLOSUM62 with gap existence 11 and extension 1.
+    pub fn blosum62() -> Self {
+        Self::with_gaps(SubstitutionMatrix::blosum62(), -12.0, -1.0)
+    }
+}
+
+impl AlignmentConfig for AaAlignmentConfig {
+    type Score = FScore;
+
+    fn get_substitution_score(&self, _pos: (usize, usize), s: u8, r: u8) -> FScore {
+        self.matrix.score(s, r)
+    }
+    fn get_subject_gap_opening_penalty(&self, _pos: usize) -> FScore {
+        self.subject_gap_penalty
+    }
+    fn get_reference_gap_opening_penalty(&self, _pos: usize) -> FScore {
+        self.reference_gap_penalty
+    }
+    fn get_subject_gap_extension_penalty(&self, _pos: usize) -> FScore {
+        self.subject_gap_extension_penalty
+    }
+    fn get_reference_gap_extension_penalty(&self, _pos: usize) -> FScore {
+        self.reference_gap_extension_penalty
+    }
+    fn accepts(&self, symbol: u8) -> bool {
+        symbol.is_ascii_alphabetic() || symbol == b'*'
+    }
+    fn is_position_independent(&self) -> bool {
+        true
+    }
+    fn validate(&self) -> Result<()> {
+        check_penalty("subject gap penalty", self.subject_gap_penalty)?;
+        check_penalty("subject gap extension penalty", self.subject_gap_extension_penalty)?;
+        check_penalty("reference gap penalty", self.reference_gap_penalty)?;
+        check_penalty("reference gap extension penalty", self.reference_gap_extension_penalty)
+    }
+}
+
+pub type GlobalAaAligner = GlobalAligner<AaAlignmentConfig>;
+
+pub type LocalAaAligner = LocalAligner<AaAlignmentConfig>;
+
+#[cfg(test)]
+mod tests {
+    use crate::aa_aligner::{AaAlignmentConfig, GlobalAaAligner, LocalAaAligner};
+    use crate::aligner::Aligner;
+    use crate::alignment::Alignment;
+    use crate::substitution_matrix::SubstitutionMatrix;
+
+    fn linear_blosum62() -> AaAlignmentConfig {
+        AaAlignmentConfig::with_gaps(SubstitutionMatrix::blosum62(), -8.0, -8.0)
+    }
+
+    #[test]
+    fn test_identity() {
+        assert_eq!(
+            GlobalAaAligner::from(AaAlignmentConfig::blosum62()).align(b"MKTAYIAK", b"MKTAYIAK").unwrap(),
+            Alignment::from("MKTAYIAK", "MKTAYIAK", 39.0)
+        )
+    }
+
+    #[test]
+    fn test_similar_residues() {
+        assert_eq!(
+            GlobalAaAligner::from(AaAlignmentConfig::blosum62()).align(b"MKTAYIAK", b"MRTAYLAK").unwrap(),
+            Alignment::from("MKTAYIAK", "MRTAYLAK", 34.0)
+        )
+    }
+
+    #[test]
+    fn test_pairs() {
+        let alignment = Alignment::from("DRSKN", "AGCGN", 0.0);
+        assert_eq!(alignment.aligned_sequences().1, "    |");
+        assert_eq!(alignment.aligned_sequences_with(&AaAlignmentConfig::blosum62()).1, "    |");
+    }
+
+    #[test]
+    fn test_global_score() {
+        assert_eq!(
+            GlobalAaAligner::from(linear_blosum62()).align(b"HEAGAWGHEE", b"PAWHEAE").unwrap().score,
+            -8.0
+        )
+    }
+
+    #[test]
+    fn test_local() {
+        assert_eq!(
+            LocalAaAligner::from(linear_blosum62()).align(b"HEAGAWGHEE", b"PAWHEAE").unwrap(),
+            Alignment::starting_at((4, 1), "AWGHE", "AW_HE", 20.0)
+        )
+    }
+
+    #[test]
+    fn test_affine_gap() {
+        assert_eq!(
+            GlobalAaAligner::from(AaAlignmentConfig::blosum62()).align(b"MKTAYIAKQR", b"MKTQR").unwrap(),
+            Alignment::from("MKTAYIAKQR", "MKT_____QR", 9.0)
+        )
+    }
+}
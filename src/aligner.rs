@@ -1,27 +1,413 @@
-use crate::alignment::Alignment;
+use crate::alignment::{Alignment, AlignmentBuilder};
 use crate::config::{AlignmentConfig};
 use crate::matrix::{Matrix, Idx};
 use crate::matrix;
+use crate::iterators::{accumulate, set_accumulated};
+use crate::element::{FScore, Element, Op, Score};
+use crate::error::{Error, Result, Sequence, check_matrix_size, check_end_score, check_sequences};
+use crate::hirschberg;
+use std::mem;
+use std::ops::Index;
 
 pub trait Aligner<C>: From<C>
     where C: AlignmentConfig {
 
-    fn align(&self, subject: &[u8], reference: &[u8]) -> Alignment {
-        let mut mtx = matrix::of(subject.len() + 1, reference.len() + 1);
+    fn align(&self, subject: &[u8], reference: &[u8]) -> Result<Alignment> {
+        self.align_with(&mut AlignerWorkspace::new(), subject, reference)
+    }
+
+    /// `align` with the score matrix kept in `workspace`, so that aligning many pairs
+    /// allocates only when a pair needs a larger matrix than any before it.
+    fn align_with(&self, workspace: &mut AlignerWorkspace<C::Score>, subject: &[u8], reference: &[u8]) -> Result<Alignment> {
+        validate(self.config(), subject, reference)?;
+        check_matrix_size(subject, reference)?;
+        let mut mtx = matrix::reusing(mem::take(&mut workspace.elements), subject.len() + 1, reference.len() + 1);
         self.fill_top_row(&mut mtx);
         self.fill_left_column(&mut mtx);
         self.fill(&mut mtx, subject, reference);
         let end_idx: Idx = self.end_idx(&mtx);
-        self.trace_back(&mtx, end_idx, &subject, &reference)
+        let alignment = self.trace_back(&mtx, end_idx, subject, reference);
+        workspace.elements = mtx.into_raw_vec();
+        alignment
+    }
+
+    /// Aligns without the score matrix, in memory linear in the sequence lengths.
+    /// The score always equals that of `align`; among equally scoring alignments
+    /// a different one may be returned.
+    fn align_in_linear_space(&self, subject: &[u8], reference: &[u8]) -> Result<Alignment>;
+
+    /// The score of `align`, computed over two rolling rows without a traceback.
+    fn score(&self, subject: &[u8], reference: &[u8]) -> Result<FScore>;
+
+    fn config(&self) -> &C;
+
+    fn fill_top_row(&self, mtx: &mut Matrix<C::Score>);
+
+    fn fill_left_column(&self, mtx: &mut Matrix<C::Score>);
+
+    fn fill(&self, mtx: &mut Matrix<C::Score>, subject: &[u8], reference: &[u8]);
+
+    fn end_idx(&self, mtx: &Matrix<C::Score>) -> Idx;
+
+    fn trace_back(&self, mtx: &Matrix<C::Score>, end_index: Idx, subject: &[u8], reference: &[u8]) -> Result<Alignment>;
+}
+
+/// Storage for the score matrix of `Aligner::align_with`, growing to the largest matrix
+/// it has held.
+#[derive(Debug, Default)]
+pub struct AlignerWorkspace<S = FScore> {
+    elements: Vec<Element<S>>,
+}
+
+impl<S> AlignerWorkspace<S> {
+    pub fn new() -> Self {
+        AlignerWorkspace { elements: Vec::new() }
     }
 
-    fn fill_top_row(&self, mtx: &mut Matrix);
+    /// Matrix cells the workspace holds without growing.
+    pub fn capacity(&self) -> usize {
+        self.elements.capacity()
+    }
+}
+
+/// End gaps that cost nothing, by the sequence they are placed in. Free subject gaps let
+/// the subject sit anywhere inside the reference, free reference gaps the other way round.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FreeEndGaps {
+    pub subject_leading: bool,
+    pub subject_trailing: bool,
+    pub reference_leading: bool,
+    pub reference_trailing: bool,
+}
+
+impl FreeEndGaps {
+    pub const NONE: Self = Self::of(false, false, false, false);
+    pub const SUBJECT_IN_REFERENCE: Self = Self::of(true, true, false, false);
+    pub const REFERENCE_IN_SUBJECT: Self = Self::of(false, false, true, true);
+    pub const OVERLAP: Self = Self::of(true, true, true, true);
 
-    fn fill_left_column(&self, mtx: &mut Matrix);
+    pub const fn of(subject_leading: bool, subject_trailing: bool, reference_leading: bool, reference_trailing: bool) -> Self {
+        FreeEndGaps { subject_leading, subject_trailing, reference_leading, reference_trailing }
+    }
+}
 
-    fn fill(&self, mtx: &mut Matrix, subject: &[u8], reference: &[u8]);
+pub struct GlobalAligner<C> {
+    pub config: C,
+    pub free_end_gaps: FreeEndGaps,
+}
 
-    fn end_idx(&self, mtx: &Matrix) -> Idx;
+impl<C: AlignmentConfig> From<C> for GlobalAligner<C> {
+    fn from(config: C) -> Self {
+        GlobalAligner { config, free_end_gaps: FreeEndGaps::NONE }
+    }
+}
+
+impl<C: AlignmentConfig> GlobalAligner<C> {
+    fn top_row_penalty(&self, n: usize) -> C::Score {
+        if self.free_end_gaps.subject_leading {
+            C::Score::ZERO
+        } else if n == 0 {
+            self.config.get_subject_gap_opening_penalty(n + 1)
+        } else {
+            self.config.get_subject_gap_extension_penalty(n + 1)
+        }
+    }
+
+    fn left_column_penalty(&self, n: usize) -> C::Score {
+        if self.free_end_gaps.reference_leading {
+            C::Score::ZERO
+        } else if n == 0 {
+            self.config.get_reference_gap_opening_penalty(n + 1)
+        } else {
+            self.config.get_reference_gap_extension_penalty(n + 1)
+        }
+    }
+}
+
+impl<C: AlignmentConfig> Aligner<C> for GlobalAligner<C> {
+    fn align_in_linear_space(&self, subject: &[u8], reference: &[u8]) -> Result<Alignment> {
+        validate(&self.config, subject, reference)?;
+        hirschberg::align_global(&self.config, self.free_end_gaps, subject, reference)
+    }
+
+    fn score(&self, subject: &[u8], reference: &[u8]) -> Result<FScore> {
+        validate(&self.config, subject, reference)?;
+        let last = (subject.len(), reference.len());
+        let mut best = C::Score::UNREACHABLE;
+        score_cells(
+            &self.config,
+            subject,
+            reference,
+            accumulate(reference.len(), |n| self.top_row_penalty(n)).collect(),
+            accumulate(subject.len(), |n| self.left_column_penalty(n)).collect(),
+            |element| element,
+            |idx, score| if idx == last ||
+                (idx.0 == last.0 && self.free_end_gaps.subject_trailing) ||
+                (idx.1 == last.1 && self.free_end_gaps.reference_trailing) {
+                best = best.max(score)
+            },
+        );
+        check_end_score(best)
+    }
+
+    fn config(&self) -> &C {
+        &self.config
+    }
+
+    fn fill_top_row(&self, mtx: &mut Matrix<C::Score>) {
+        set_accumulated(
+            accumulate(mtx.cols() - 1, |n| self.top_row_penalty(n)),
+            mtx.row_mut(0).iter_mut(),
+            deletion,
+        )
+    }
+
+    fn fill_left_column(&self, mtx: &mut Matrix<C::Score>) {
+        set_accumulated(
+            accumulate(mtx.rows() - 1, |n| self.left_column_penalty(n)),
+            mtx.column_mut(0).iter_mut(),
+            insertion,
+        );
+    }
+
+    fn fill(&self, mtx: &mut Matrix<C::Score>, subject: &[u8], reference: &[u8]) {
+        fill_cells(&self.config, mtx, subject, reference, |element| element)
+    }
+
+    fn end_idx(&self, mtx: &Matrix<C::Score>) -> Idx {
+        let last = (mtx.rows() - 1, mtx.cols() - 1);
+        let last_row = (0..mtx.cols())
+            .filter(|_| self.free_end_gaps.subject_trailing)
+            .map(|col| (last.0, col));
+        let last_column = (0..mtx.rows())
+            .filter(|_| self.free_end_gaps.reference_trailing)
+            .map(|row| (row, last.1));
+        last_row.chain(last_column)
+            .fold(last, |best, idx| if mtx[idx].score > mtx[best].score { idx } else { best })
+    }
+
+    fn trace_back(&self, mtx: &Matrix<C::Score>, end_index: Idx, subject: &[u8], reference: &[u8]) -> Result<Alignment> {
+        let mut builder = AlignmentBuilder::new(subject, reference);
+        let mut cursor = (mtx.rows() - 1, mtx.cols() - 1);
+        while cursor != end_index {
+            let op = if cursor.0 > end_index.0 { Op::INSERT } else { Op::DELETE };
+            builder.take(op, cursor)?;
+            cursor = matrix::move_back(op, cursor);
+        }
+        trace(mtx, end_index, builder)
+    }
+}
+
+pub struct LocalAligner<C> {
+    pub config: C
+}
+
+impl<C: AlignmentConfig> From<C> for LocalAligner<C> {
+    fn from(config: C) -> Self {
+        LocalAligner { config }
+    }
+}
+
+impl<C: AlignmentConfig> Aligner<C> for LocalAligner<C> {
+    fn align_in_linear_space(&self, subject: &[u8], reference: &[u8]) -> Result<Alignment> {
+        validate(&self.config, subject, reference)?;
+        hirschberg::align_local(&self.config, subject, reference)
+    }
+
+    fn score(&self, subject: &[u8], reference: &[u8]) -> Result<FScore> {
+        validate(&self.config, subject, reference)?;
+        let mut best = C::Score::ZERO;
+        score_cells(
+            &self.config,
+            subject,
+            reference,
+            vec![C::Score::ZERO; reference.len() + 1],
+            vec![C::Score::ZERO; subject.len() + 1],
+            floor,
+            |_, score| best = best.max(score),
+        );
+        check_end_score(best)
+    }
+
+    fn config(&self) -> &C {
+        &self.config
+    }
+
+    fn fill_top_row(&self, mtx: &mut Matrix<C::Score>) {
+        mtx.row_mut(0).fill(Element::default())
+    }
+
+    fn fill_left_column(&self, mtx: &mut Matrix<C::Score>) {
+        mtx.column_mut(0).fill(Element::default())
+    }
+
+    fn fill(&self, mtx: &mut Matrix<C::Score>, subject: &[u8], reference: &[u8]) {
+        fill_cells(&self.config, mtx, subject, reference, floor)
+    }
+
+    fn end_idx(&self, mtx: &Matrix<C::Score>) -> Idx {
+        mtx.indexed_iter()
+            .fold(((0, 0), Element::default()), |best, (idx, element)|
+                if element.score > best.1.score { (idx, *element) } else { best },
+            )
+            .0
+    }
+
+    fn trace_back(&self, mtx: &Matrix<C::Score>, end_index: Idx, subject: &[u8], reference: &[u8]) -> Result<Alignment> {
+        trace(mtx, end_index, AlignmentBuilder::new(subject, reference))
+    }
+}
+
+/// Gotoh fill: the best insertion scores of the previous row and the best deletion score
+/// of the current row are kept alongside the matrix, and each cell records whether
+/// the gaps ending in it extend the gaps of its upper and left neighbours.
+fn fill_cells<C: AlignmentConfig>(
+    config: &C,
+    mtx: &mut Matrix<C::Score>,
+    subject: &[u8],
+    reference: &[u8],
+    bound: fn(Element<C::Score>) -> Element<C::Score>,
+) {
+    let mut insertion_scores = vec![C::Score::UNREACHABLE; mtx.cols()];
+    for row in 1..mtx.rows() {
+        let s = subject[row - 1];
+        let mut deletion_score = C::Score::UNREACHABLE;
+        for col in 1..mtx.cols() {
+            let r = reference[col - 1];
+            let (insertion_score, extends_insertion) = gap(
+                mtx[(row - 1, col)] +
+                    config.get_reference_gap_opening_penalty(row),
+                insertion_scores[col].plus(
+                    config.get_reference_gap_extension_penalty(row)),
+            );
+            let (next_deletion_score, extends_deletion) = gap(
+                mtx[(row, col - 1)] +
+                    config.get_subject_gap_opening_penalty(col),
+                deletion_score.plus(
+                    config.get_subject_gap_extension_penalty(col)),
+            );
+            insertion_scores[col] = insertion_score;
+            deletion_score = next_deletion_score;
+            let element = select(
+                mtx[(row - 1, col - 1)] +
+                    config.get_substitution_score((row, col), s, r),
+                insertion_score,
+                deletion_score,
+            );
+            mtx[(row, col)] = bound(Element { extends_insertion, extends_deletion, ..element })
+        }
+    }
+}
+
+/// The scores of `fill_cells`, one row at a time; `visit` sees every cell including the borders.
+fn score_cells<C: AlignmentConfig>(
+    config: &C,
+    subject: &[u8],
+    reference: &[u8],
+    top_row: Vec<C::Score>,
+    left_column: Vec<C::Score>,
+    bound: fn(Element<C::Score>) -> Element<C::Score>,
+    mut visit: impl FnMut(Idx, C::Score),
+) {
+    let mut above = top_row;
+    let mut current = vec![C::Score::ZERO; above.len()];
+    let mut insertion_scores = vec![C::Score::UNREACHABLE; above.len()];
+    above.iter()
+        .enumerate()
+        .for_each(|(col, &score)| visit((0, col), score));
+    for row in 1..left_column.len() {
+        let s = subject[row - 1];
+        let mut deletion_score = C::Score::UNREACHABLE;
+        current[0] = left_column[row];
+        visit((row, 0), current[0]);
+        for col in 1..above.len() {
+            let insertion_score = gap(
+                above[col].plus(config.get_reference_gap_opening_penalty(row)),
+                insertion_scores[col].plus(config.get_reference_gap_extension_penalty(row)),
+            ).0;
+            deletion_score = gap(
+                current[col - 1].plus(config.get_subject_gap_opening_penalty(col)),
+                deletion_score.plus(config.get_subject_gap_extension_penalty(col)),
+            ).0;
+            insertion_scores[col] = insertion_score;
+            current[col] = bound(select(
+                above[col - 1].plus(config.get_substitution_score((row, col), s, reference[col - 1])),
+                insertion_score,
+                deletion_score,
+            )).score;
+            visit((row, col), current[col]);
+        }
+        std::mem::swap(&mut above, &mut current);
+    }
+}
+
+pub(crate) fn gap<S: Score>(opening_score: S, extension_score: S) -> (S, bool) {
+    if extension_score > opening_score {
+        (extension_score, true)
+    } else {
+        (opening_score, false)
+    }
+}
+
+/// Checks the configuration, and that both sequences are non-empty and only hold accepted symbols.
+pub(crate) fn validate<C: AlignmentConfig>(config: &C, subject: &[u8], reference: &[u8]) -> Result<()> {
+    config.validate()?;
+    check_sequences(subject, reference, |symbol| config.accepts(symbol))?;
+    let lengths = [
+        (Sequence::Subject, config.subject_length(), subject.len()),
+        (Sequence::Reference, config.reference_length(), reference.len()),
+    ];
+    for &(sequence, expected, found) in lengths.iter() {
+        match expected {
+            Some(expected) if expected != found => return Err(Error::SequenceLength { sequence, expected, found }),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn trace<S: Score, M>(mtx: &M, end_index: Idx, mut builder: AlignmentBuilder) -> Result<Alignment>
+    where M: Index<Idx, Output=Element<S>> {
+    let score = check_end_score(mtx[end_index].score)?;
+    let mut cursor = end_index;
+    let mut open_gap: Option<Op> = None;
+    while open_gap.is_some() || mtx[cursor].op != Op::START {
+        let element = mtx[cursor];
+        let op = open_gap.unwrap_or(element.op);
+        builder.take(op, cursor)?;
+        open_gap = match op {
+            Op::INSERT if element.extends_insertion => Some(op),
+            Op::DELETE if element.extends_deletion => Some(op),
+            _ => None
+        };
+        cursor = matrix::move_back(op, cursor);
+    }
+    builder.take(Op::START, cursor)?;
+    Ok(builder.build(score))
+}
+
+pub(crate) fn floor<S: Score>(element: Element<S>) -> Element<S> {
+    if element.score > S::ZERO { element } else { Element::default() }
+}
+
+pub(crate) fn select<S: Score>(substitution_score: S, insertion_score: S, deletion_score: S) -> Element<S> {
+    if substitution_score >= insertion_score && substitution_score >= deletion_score {
+        substitution(substitution_score)
+    } else if insertion_score >= deletion_score {
+        insertion(insertion_score)
+    } else {
+        deletion(deletion_score)
+    }
+}
+
+pub fn insertion<S: Score>(score: S) -> Element<S> {
+    Element { op: Op::INSERT, score, ..Element::default() }
+}
+
+pub fn deletion<S: Score>(score: S) -> Element<S> {
+    Element { op: Op::DELETE, score, ..Element::default() }
+}
 
-    fn trace_back(&self, mtx: &Matrix, end_index: Idx, subject: &[u8], reference: &[u8]) -> Alignment;
+pub fn substitution<S: Score>(score: S) -> Element<S> {
+    Element { op: Op::MATCH, score, ..Element::default() }
 }
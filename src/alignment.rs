@@ -1,9 +1,17 @@
-use crate::element::{FScore, Op};
+use crate::config::AlignmentConfig;
+use crate::element::{FScore, Op, Score};
+use crate::error::{Error, Result};
 use crate::matrix::Idx;
 use core::iter;
 
 pub const GAP: char = '_';
 
+/// Whether an aligned pair is a match in scoring, pictures, statistics, CIGAR strings and
+/// SAM tags: the same symbol, ignoring case.
+pub fn same_symbol(s: u8, r: u8) -> bool {
+    s.eq_ignore_ascii_case(&r)
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Anchor {
     pub idx: Idx,
@@ -13,7 +21,9 @@ pub struct Anchor {
 }
 
 impl Anchor {
-    const START: Self = Self { idx: (0, 0), op: Op::START, r: 0, s: 0 };
+    fn start(idx: Idx) -> Self {
+        Anchor { idx, op: Op::START, r: 0, s: 0 }
+    }
 
     fn from(idx: Idx, op: Op, s: char, r: char) -> Self {
         Anchor { idx, op, s: s as u8, r: r as u8 }
@@ -28,19 +38,64 @@ pub struct Alignment {
 
 impl Alignment {
     pub fn from(subject: &str, reference: &str, score: FScore) -> Self {
+        Self::starting_at((0, 0), subject, reference, score)
+    }
+
+    pub fn starting_at(start: Idx, subject: &str, reference: &str, score: FScore) -> Self {
         Alignment {
             score,
-            anchors: to_anchors(subject, reference),
+            anchors: to_anchors(start, subject, reference),
+        }
+    }
+
+    /// Subject and reference offsets of the first aligned pair.
+    pub fn start(&self) -> Idx {
+        self.anchors.last().map_or((0, 0), |a| a.idx)
+    }
+
+    /// Subject and reference offsets just past the last aligned pair.
+    pub fn end(&self) -> Idx {
+        self.anchors.first().map_or((0, 0), |a| a.idx)
+    }
+
+    /// The score of the aligned pairs under `config`, every run of gaps opening once.
+    /// End gaps are charged, even where the aligner that built the alignment left them free.
+    pub fn rescore<C: AlignmentConfig>(&self, config: &C) -> FScore {
+        let mut previous = Op::START;
+        let mut score = C::Score::ZERO;
+        for anchor in self.anchors.iter().rev().skip(1) {
+            let (row, col) = anchor.idx;
+            score = score.plus(match (anchor.op, previous == anchor.op) {
+                (Op::MATCH, _) => config.get_substitution_score(anchor.idx, anchor.s, anchor.r),
+                (Op::INSERT, false) => config.get_reference_gap_opening_penalty(row),
+                (Op::INSERT, true) => config.get_reference_gap_extension_penalty(row),
+                (Op::DELETE, false) => config.get_subject_gap_opening_penalty(col),
+                (Op::DELETE, true) => config.get_subject_gap_extension_penalty(col),
+                (Op::START, _) => C::Score::ZERO,
+            });
+            previous = anchor.op;
         }
+        score.to_f64()
     }
 
+    /// The subject symbol, `match_symbol` or a space, and the reference symbol of every column.
+    /// Symbols match by `same_symbol`.
     pub fn pairs(&self, match_symbol: char) -> impl Iterator<Item=(char, char, char)> + '_ {
+        self.pairs_by(match_symbol, same_symbol)
+    }
+
+    /// `pairs` with symbols matching by `config.matches`, as IUPAC codes do under their scoring.
+    pub fn pairs_with<'a, C: AlignmentConfig>(&'a self, config: &'a C, match_symbol: char) -> impl Iterator<Item=(char, char, char)> + 'a {
+        self.pairs_by(match_symbol, move |s, r| config.matches(s, r))
+    }
+
+    fn pairs_by<'a>(&'a self, match_symbol: char, matches: impl Fn(u8, u8) -> bool + 'a) -> impl Iterator<Item=(char, char, char)> + 'a {
         self.anchors.iter()
             .rev()
             .skip(1)
             .map(move |a| (
                 a.s as char,
-                if a.s == a.r { match_symbol } else { ' ' },
+                if a.op == Op::MATCH && matches(a.s, a.r) { match_symbol } else { ' ' },
                 a.r as char
             ))
     }
@@ -58,12 +113,11 @@ impl Alignment {
     }
 
     pub fn aligned_sequences(&self) -> (String, String, String) {
-        let pairs: Vec<(char, char, char)> = self.pairs('|').collect();
-        (
-            pairs.iter().map(|p| p.0).collect(),
-            pairs.iter().map(|p| p.1).collect(),
-            pairs.iter().map(|p| p.2).collect()
-        )
+        unzip(self.pairs('|'))
+    }
+
+    pub fn aligned_sequences_with<C: AlignmentConfig>(&self, config: &C) -> (String, String, String) {
+        unzip(self.pairs_with(config, '|'))
     }
 }
 
@@ -82,14 +136,21 @@ impl<'a> AlignmentBuilder<'a> {
         }
     }
 
-    pub fn take(&mut self, op: Op, idx: Idx) {
+    /// Adds the pair `op` ends with at `idx`, the alignment being built from its end.
+    pub fn take(&mut self, op: Op, idx: Idx) -> Result<()> {
+        if idx.0 > self.subject.len() || idx.1 > self.reference.len() {
+            return Err(Error::OutOfBounds(idx));
+        }
+        let s = || idx.0.checked_sub(1).map(|i| self.subject[i]).ok_or(Error::OutOfBounds(idx));
+        let r = || idx.1.checked_sub(1).map(|i| self.reference[i]).ok_or(Error::OutOfBounds(idx));
         let anchor: Anchor = match op {
-            Op::MATCH => Anchor { idx, op, s: self.subject[idx.0 - 1], r: self.reference[idx.1 - 1] },
-            Op::DELETE => Anchor { idx, op, s: GAP as u8, r: self.reference[idx.1 - 1] },
-            Op::INSERT => Anchor { idx, op, s: self.subject[idx.0 - 1], r: GAP as u8 },
+            Op::MATCH => Anchor { idx, op, s: s()?, r: r()? },
+            Op::DELETE => Anchor { idx, op, s: GAP as u8, r: r()? },
+            Op::INSERT => Anchor { idx, op, s: s()?, r: GAP as u8 },
             Op::START => Anchor { idx, op, s: 0, r: 0 }
         };
         self.anchors.push(anchor);
+        Ok(())
     }
 
     pub fn build(self, score: FScore) -> Alignment {
@@ -100,16 +161,25 @@ impl<'a> AlignmentBuilder<'a> {
     }
 }
 
-fn to_anchors(subject: &str, reference: &str) -> Vec<Anchor> {
-    let mut anchors: Vec<Anchor> = iter::once(Anchor::START)
-        .chain(from_strings(subject, reference))
+fn unzip(pairs: impl Iterator<Item=(char, char, char)>) -> (String, String, String) {
+    let pairs: Vec<(char, char, char)> = pairs.collect();
+    (
+        pairs.iter().map(|p| p.0).collect(),
+        pairs.iter().map(|p| p.1).collect(),
+        pairs.iter().map(|p| p.2).collect()
+    )
+}
+
+fn to_anchors(start: Idx, subject: &str, reference: &str) -> Vec<Anchor> {
+    let mut anchors: Vec<Anchor> = iter::once(Anchor::start(start))
+        .chain(from_strings(start, subject, reference))
         .collect();
     anchors.reverse();
     anchors
 }
 
-fn from_strings<'a>(subject: &'a str, reference: &'a str) -> impl Iterator<Item=Anchor> + 'a {
-    let mut inc = IdxIncrementer::START;
+fn from_strings<'a>(start: Idx, subject: &'a str, reference: &'a str) -> impl Iterator<Item=Anchor> + 'a {
+    let mut inc = IdxIncrementer::at(start);
     subject.chars()
         .zip(reference.chars())
         .map(move |(s, r)|
@@ -136,7 +206,9 @@ struct IdxIncrementer {
 }
 
 impl IdxIncrementer {
-    const START: Self = Self { r_inc: 0, s_inc: 0 };
+    fn at(start: Idx) -> Self {
+        IdxIncrementer { s_inc: start.0, r_inc: start.1 }
+    }
 
     fn with(&mut self, s: char, r: char) -> Idx {
         (
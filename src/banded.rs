@@ -0,0 +1,265 @@
+use crate::aligner::{gap, select, trace, deletion, insertion, validate};
+use crate::alignment::{Alignment, AlignmentBuilder};
+use crate::config::AlignmentConfig;
+use crate::element::{Element, Score};
+use crate::error::{Error, Result};
+use crate::matrix::Idx;
+use std::ops::{Index, IndexMut, Range};
+
+/// The cells whose diagonal, `column - row`, is within `width` of `offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub width: usize,
+    pub offset: isize,
+}
+
+impl Band {
+    pub fn around_main_diagonal(width: usize) -> Self {
+        Band { width, offset: 0 }
+    }
+
+    pub fn contains(&self, (row, col): Idx) -> bool {
+        self.distance((row, col)).unsigned_abs() <= self.width
+    }
+
+    fn distance(&self, (row, col): Idx) -> isize {
+        col as isize - row as isize - self.offset
+    }
+
+    fn columns(&self, row: usize, num_columns: usize) -> Range<usize> {
+        let center = row as isize + self.offset;
+        let first = (center - self.width as isize).max(0);
+        let last = (center + self.width as isize + 1).min(num_columns as isize);
+        first as usize..last.max(first) as usize
+    }
+}
+
+/// A global alignment found inside a band. When the path runs along the edge of the band,
+/// a better one may lie outside of it, and the pair is worth aligning again with a wider band.
+#[derive(Debug, PartialEq)]
+pub struct BandedAlignment {
+    pub alignment: Alignment,
+    pub touches_band_edge: bool,
+}
+
+/// Global aligner that only fills the cells of a band, storing `2 * width + 1` cells per row.
+pub struct BandedAligner<C> {
+    pub config: C,
+    pub band: Band,
+}
+
+impl<C: AlignmentConfig> BandedAligner<C> {
+    /// Fails with `Error::OutsideBand` when the band does not hold both the first and the last cell.
+    pub fn align(&self, subject: &[u8], reference: &[u8]) -> Result<BandedAlignment> {
+        validate(&self.config, subject, reference)?;
+        let last = (subject.len(), reference.len());
+        if !self.band.contains((0, 0)) || !self.band.contains(last) {
+            return Err(Error::OutsideBand);
+        }
+        let mut mtx = BandedMatrix::of(self.band, subject.len() + 1, reference.len() + 1);
+        self.fill(&mut mtx, subject, reference);
+        let alignment = trace(&mtx, last, AlignmentBuilder::new(subject, reference))?;
+        let touches_band_edge = alignment.anchors.iter()
+            .any(|anchor| self.on_edge(anchor.idx));
+        Ok(BandedAlignment { alignment, touches_band_edge })
+    }
+
+    fn fill(&self, mtx: &mut BandedMatrix<C::Score>, subject: &[u8], reference: &[u8]) {
+        let config = &self.config;
+        let mut insertion_scores = vec![C::Score::UNREACHABLE; mtx.cols];
+        for col in self.band.columns(0, mtx.cols).skip(1) {
+            let penalty = if col == 1 {
+                config.get_subject_gap_opening_penalty(col)
+            } else {
+                config.get_subject_gap_extension_penalty(col)
+            };
+            mtx[(0, col)] = deletion(mtx[(0, col - 1)] + penalty);
+        }
+        for row in 1..mtx.rows {
+            let mut deletion_score = C::Score::UNREACHABLE;
+            for col in self.band.columns(row, mtx.cols) {
+                if !self.band.contains((row - 1, col)) {
+                    insertion_scores[col] = C::Score::UNREACHABLE;
+                }
+                if col == 0 {
+                    let penalty = if row == 1 {
+                        config.get_reference_gap_opening_penalty(row)
+                    } else {
+                        config.get_reference_gap_extension_penalty(row)
+                    };
+                    mtx[(row, col)] = insertion(mtx[(row - 1, col)] + penalty);
+                    continue;
+                }
+                let (insertion_score, extends_insertion) = gap(
+                    mtx[(row - 1, col)] +
+                        config.get_reference_gap_opening_penalty(row),
+                    insertion_scores[col].plus(
+                        config.get_reference_gap_extension_penalty(row)),
+                );
+                let (next_deletion_score, extends_deletion) = gap(
+                    mtx[(row, col - 1)] +
+                        config.get_subject_gap_opening_penalty(col),
+                    deletion_score.plus(
+                        config.get_subject_gap_extension_penalty(col)),
+                );
+                insertion_scores[col] = insertion_score;
+                deletion_score = next_deletion_score;
+                let element = select(
+                    mtx[(row - 1, col - 1)] +
+                        config.get_substitution_score((row, col), subject[row - 1], reference[col - 1]),
+                    insertion_score,
+                    deletion_score,
+                );
+                mtx[(row, col)] = Element { extends_insertion, extends_deletion, ..element }
+            }
+        }
+    }
+
+    /// Whether a neighbour of the cell is left out by the band rather than by the matrix bounds.
+    fn on_edge(&self, idx: Idx) -> bool {
+        let distance = self.band.distance(idx);
+        let width = self.band.width as isize;
+        (distance == width && idx.0 > 0) || (distance == -width && idx.1 > 0)
+    }
+}
+
+/// Row-major storage of the cells in a band; cells outside it read as unreachable.
+struct BandedMatrix<S> {
+    band: Band,
+    rows: usize,
+    cols: usize,
+    elements: Vec<Element<S>>,
+    outside: Element<S>,
+}
+
+impl<S: Score> BandedMatrix<S> {
+    fn of(band: Band, rows: usize, cols: usize) -> Self {
+        BandedMatrix {
+            band,
+            rows,
+            cols,
+            elements: vec![Element::default(); rows * Self::stride(band)],
+            outside: Element { score: S::UNREACHABLE, ..Element::default() },
+        }
+    }
+
+    fn stride(band: Band) -> usize {
+        2 * band.width + 1
+    }
+
+    fn position(&self, idx: Idx) -> usize {
+        let column_in_band = self.band.distance(idx) + self.band.width as isize;
+        idx.0 * Self::stride(self.band) + column_in_band as usize
+    }
+}
+
+impl<S: Score> Index<Idx> for BandedMatrix<S> {
+    type Output = Element<S>;
+
+    fn index(&self, idx: Idx) -> &Element<S> {
+        if self.band.contains(idx) {
+            &self.elements[self.position(idx)]
+        } else {
+            &self.outside
+        }
+    }
+}
+
+impl<S: Score> IndexMut<Idx> for BandedMatrix<S> {
+    fn index_mut(&mut self, idx: Idx) -> &mut Element<S> {
+        let position = self.position(idx);
+        &mut self.elements[position]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::Aligner;
+    use crate::alignment::Alignment;
+    use crate::banded::{Band, BandedAlignment};
+    use crate::error::Error;
+    use crate::nt_aligner::{BandedNtAligner, GlobalNtAligner, NtAlignmentConfig};
+
+    const AFFINE_CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -2.0,
+        subject_gap_extension_penalty: -0.5,
+        reference_gap_penalty: -2.0,
+        reference_gap_extension_penalty: -0.5,
+    };
+
+    fn banded(width: usize, offset: isize) -> BandedNtAligner {
+        BandedNtAligner { config: AFFINE_CONFIG, band: Band { width, offset } }
+    }
+
+    #[test]
+    fn test_same_as_global() {
+        let subject = b"ACGTTGCAAGTCCGATGCAT";
+        let reference = b"ACGTTGCAGTCCGATGGCAT";
+        assert_eq!(
+            banded(3, 0).align(subject, reference),
+            Ok(BandedAlignment {
+                alignment: GlobalNtAligner::from(AFFINE_CONFIG).align(subject, reference).unwrap(),
+                touches_band_edge: false,
+            })
+        )
+    }
+
+    #[test]
+    fn test_band_covering_matrix() {
+        let pairs: [(&[u8], &[u8]); 4] = [
+            (b"GCTTGT", b"TTTA"),
+            (b"CGGTTAG", b"TTGC"),
+            (b"A", b"ACGTTCA"),
+            (b"CTAG", b"CGTG"),
+        ];
+        for (subject, reference) in pairs.iter() {
+            assert_eq!(
+                banded(subject.len() + reference.len(), 0).align(subject, reference).unwrap().alignment,
+                GlobalNtAligner::from(AFFINE_CONFIG).align(subject, reference).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_touches_band_edge() {
+        let subject = b"AAAACCCCGGGGTTTT";
+        let reference = b"CCCCGGGGTTTTAAAA";
+        let narrow = banded(2, 0).align(subject, reference).unwrap();
+        let wide = banded(8, 0).align(subject, reference).unwrap();
+        assert!(narrow.touches_band_edge);
+        assert!(!wide.touches_band_edge);
+        assert!(narrow.alignment.score < wide.alignment.score);
+        assert_eq!(wide.alignment, GlobalNtAligner::from(AFFINE_CONFIG).align(subject, reference).unwrap());
+    }
+
+    #[test]
+    fn test_offset() {
+        let subject = b"ACGTACGT";
+        let reference = b"ACGTTTTACGT";
+        assert_eq!(banded(2, 0).align(subject, reference), Err(Error::OutsideBand));
+        assert_eq!(
+            banded(3, 2).align(subject, reference),
+            Ok(BandedAlignment {
+                alignment: Alignment::from("ACG___TACGT", "ACGTTTTACGT", 5.0),
+                touches_band_edge: false,
+            })
+        )
+    }
+
+    #[test]
+    fn test_band_edge_at_matrix_bounds() {
+        assert_eq!(
+            banded(0, 0).align(b"ACGT", b"AGGT"),
+            Ok(BandedAlignment {
+                alignment: Alignment::from("ACGT", "AGGT", 2.0),
+                touches_band_edge: true,
+            })
+        );
+        assert_eq!(
+            banded(1, 0).align(b"A", b"T"),
+            Ok(BandedAlignment { alignment: Alignment::from("A", "T", -1.0), touches_band_edge: false })
+        );
+    }
+}
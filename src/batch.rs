@@ -0,0 +1,153 @@
+use crate::aligner::{Aligner, AlignerWorkspace};
+use crate::alignment::Alignment;
+use crate::config::AlignmentConfig;
+use crate::error::Result;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::iter;
+use std::sync::{Mutex, PoisonError};
+
+/// Pairs taken from the input at a time; the pool aligns each such chunk in parallel.
+const CHUNK_SIZE: usize = 1024;
+
+/// Aligns pairs across a thread pool, every worker thread reusing an `AlignerWorkspace` of its own
+/// from pair to pair.
+pub struct BatchAligner<A> {
+    pub aligner: A,
+    pool: ThreadPool,
+}
+
+impl<A> BatchAligner<A> {
+    /// Runs `threads` workers, or one per core when `threads` is 0.
+    pub fn new(aligner: A, threads: usize) -> std::result::Result<Self, ThreadPoolBuildError> {
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+        Ok(BatchAligner { aligner, pool })
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Aligns the subject and reference of every pair, yielding each pair with its alignment
+    /// in input order. Pairs are read a chunk ahead of the results.
+    pub fn align<'a, C, I, S, R>(&'a self, pairs: I) -> impl Iterator<Item=(S, R, Result<Alignment>)> + 'a
+        where A: Aligner<C> + Sync,
+              C: AlignmentConfig,
+              C::Score: Send + 'a,
+              I: IntoIterator<Item=(S, R)>,
+              I::IntoIter: 'a,
+              S: AsRef<[u8]> + Send + 'a,
+              R: AsRef<[u8]> + Send + 'a {
+        self.align_by(pairs, |aligner, workspace, (subject, reference)| {
+            aligner.align_with(workspace, subject.as_ref(), reference.as_ref())
+        }).map(|((subject, reference), alignment)| (subject, reference, alignment))
+    }
+
+    /// `align` for items that `align_item` aligns with the aligner, e.g. through a configuration
+    /// of their own built on the aligner's, or on both strands.
+    pub fn align_by<'a, T, I, F, S, O>(&'a self, items: I, align_item: F) -> impl Iterator<Item=(T, O)> + 'a
+        where A: Sync,
+              I: IntoIterator<Item=T>,
+              I::IntoIter: 'a,
+              T: Send + 'a,
+              F: Fn(&A, &mut AlignerWorkspace<S>, &T) -> O + Sync + 'a,
+              S: Send + 'a,
+              O: Send + 'a {
+        let mut items = items.into_iter();
+        // A workspace per worker, by rayon's index of the worker thread.
+        let workspaces: Vec<Mutex<AlignerWorkspace<S>>> = (0..self.threads())
+            .map(|_| Mutex::new(AlignerWorkspace::new()))
+            .collect();
+        iter::from_fn(move || {
+            let chunk: Vec<T> = items.by_ref().take(CHUNK_SIZE).collect();
+            if chunk.is_empty() {
+                return None;
+            }
+            Some(self.pool.install(|| chunk.into_par_iter()
+                .map(|item| {
+                    let worker = rayon::current_thread_index().unwrap_or(0);
+                    let mut workspace = workspaces[worker].lock().unwrap_or_else(PoisonError::into_inner);
+                    let alignment = align_item(&self.aligner, &mut workspace, &item);
+                    (item, alignment)
+                })
+                .collect::<Vec<_>>()))
+        }).flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::{Aligner, GlobalAligner};
+    use crate::batch::BatchAligner;
+    use crate::error::{Error, Sequence};
+    use crate::nt_aligner::{GlobalNtAligner, NtAlignmentConfig};
+    use crate::quality::QualityConfig;
+
+    const CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -2.0,
+        subject_gap_extension_penalty: -0.5,
+        reference_gap_penalty: -2.0,
+        reference_gap_extension_penalty: -0.5,
+    };
+
+    fn pairs(count: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..count)
+            .map(|i| {
+                let subject = b"ACGTTGCAAGTCCGATGCAT".repeat(1 + i % 7);
+                let reference = b"ACGTTGCAGTCCGATGGCAT".repeat(1 + i % 5);
+                (subject[i % 3..].to_vec(), reference)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_as_align_in_order() {
+        let pairs = pairs(1500);
+        let batch = BatchAligner::new(GlobalNtAligner::from(CONFIG), 4).unwrap();
+        assert_eq!(batch.threads(), 4);
+        let mut count = 0;
+        for ((subject, reference, alignment), (expected_subject, expected_reference)) in batch.align(pairs.iter().map(|(s, r)| (s, r))).zip(pairs.iter()) {
+            assert_eq!((subject, reference), (expected_subject, expected_reference));
+            assert_eq!(alignment, batch.aligner.align(subject, reference));
+            count += 1;
+        }
+        assert_eq!(count, pairs.len());
+    }
+
+    #[test]
+    fn test_errors_in_place() {
+        let batch = BatchAligner::new(GlobalNtAligner::from(CONFIG), 2).unwrap();
+        let results: Vec<_> = batch.align(vec![(&b"ACGT"[..], &b"ACGT"[..]), (b"", b"ACGT"), (b"ACXT", b"ACGT")])
+            .map(|(_, _, alignment)| alignment.map(|a| a.score))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                Ok(4.0),
+                Err(Error::EmptySequence(Sequence::Subject)),
+                Err(Error::InvalidSymbol { sequence: Sequence::Subject, position: 2, symbol: b'X' }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_align_by() {
+        let batch = BatchAligner::new(GlobalNtAligner::from(CONFIG), 2).unwrap();
+        let items = vec![(&b"ACGT"[..], &b"IIII"[..]), (b"ACTT", b"II!I")];
+        let scores: Vec<_> = batch.align_by(items, |aligner, workspace, (subject, quality)| {
+            GlobalAligner::from(QualityConfig::new(&aligner.config, quality)).align_with(workspace, subject, b"ACGT")
+        })
+            .map(|(_, alignment)| alignment.unwrap().score.round())
+            .collect();
+        assert_eq!(scores, vec![4.0, 3.0]);
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let batch = BatchAligner::new(GlobalNtAligner::from(CONFIG), 0).unwrap();
+        assert!(batch.threads() > 0);
+        assert_eq!(batch.align(Vec::<(Vec<u8>, Vec<u8>)>::new()).count(), 0);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::alignment::{Alignment, AlignmentBuilder, same_symbol};
+use crate::element::{FScore, Op};
+use crate::matrix::{self, Idx};
+use std::fmt;
+use std::iter;
+
+#[derive(Debug, PartialEq)]
+pub struct CigarParseError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CigarParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CIGAR offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for CigarParseError {}
+
+impl Alignment {
+    /// CIGAR with `M` for every aligned pair, `I` for subject bases and `D` for reference bases
+    /// missing from the other sequence.
+    pub fn to_cigar(&self) -> String {
+        run_length(self.ops().map(|(op, _)| basic(op)))
+    }
+
+    /// CIGAR telling matches `=` from mismatches `X`, with the subject bases outside
+    /// the alignment soft-clipped as `S`.
+    pub fn to_extended_cigar(&self, subject_length: usize) -> String {
+        let (start, end) = (self.start().0, self.end().0);
+        run_length(
+            iter::repeat_n('S', start)
+                .chain(self.ops().map(|(op, equal)| extended(op, equal)))
+                .chain(iter::repeat_n('S', subject_length.saturating_sub(end)))
+        )
+    }
+
+    /// Rebuilds the alignment a basic or extended CIGAR describes, starting at `start`.
+    /// Soft clips must cover exactly the subject bases outside the alignment.
+    pub fn from_cigar(
+        cigar: &str,
+        subject: &[u8],
+        reference: &[u8],
+        start: Idx,
+        score: FScore,
+    ) -> Result<Self, CigarParseError> {
+        let operations = parse(cigar)?;
+        if start.0 > subject.len() || start.1 > reference.len() {
+            return Err(parse_error(0, "alignment starts past the sequence ends"));
+        }
+        let mut cursor = start;
+        let mut steps = Vec::with_capacity(subject.len() + reference.len());
+        for (i, &(offset, count, code)) in operations.iter().enumerate() {
+            let op = match code {
+                'S' if i == 0 => {
+                    if count != start.0 {
+                        return Err(parse_error(offset, "soft clip does not reach the alignment start"));
+                    }
+                    continue;
+                }
+                'S' if i == operations.len() - 1 => {
+                    if cursor.0 + count != subject.len() {
+                        return Err(parse_error(offset, "soft clip does not reach the subject end"));
+                    }
+                    continue;
+                }
+                'S' => return Err(parse_error(offset, "soft clip inside the alignment")),
+                'M' | '=' | 'X' => Op::MATCH,
+                'I' => Op::INSERT,
+                'D' => Op::DELETE,
+                _ => return Err(parse_error(offset, &format!("unsupported operation {}", code)))
+            };
+            for _ in 0..count {
+                cursor = matrix::move_forward(op, cursor);
+                if cursor.0 > subject.len() || cursor.1 > reference.len() {
+                    return Err(parse_error(offset, "alignment runs past the sequence ends"));
+                }
+                let mismatched = match code {
+                    '=' => !same_symbol(subject[cursor.0 - 1], reference[cursor.1 - 1]),
+                    'X' => same_symbol(subject[cursor.0 - 1], reference[cursor.1 - 1]),
+                    _ => false
+                };
+                if mismatched {
+                    return Err(parse_error(offset, &format!("{} at subject position {}", code, cursor.0)));
+                }
+                steps.push((op, cursor));
+            }
+        }
+        let mut builder = AlignmentBuilder::new(subject, reference);
+        steps.into_iter()
+            .rev()
+            .chain(iter::once((Op::START, start)))
+            .try_for_each(|(op, idx)| builder.take(op, idx))
+            .map_err(|e| parse_error(0, &e.to_string()))?;
+        Ok(builder.build(score))
+    }
+
+    fn ops(&self) -> impl Iterator<Item=(Op, bool)> + '_ {
+        self.anchors.iter()
+            .rev()
+            .skip(1)
+            .map(|a| (a.op, same_symbol(a.s, a.r)))
+    }
+}
+
+pub(crate) fn basic(op: Op) -> char {
+    match op {
+        Op::INSERT => 'I',
+        Op::DELETE => 'D',
+        _ => 'M'
+    }
+}
+
+fn extended(op: Op, equal: bool) -> char {
+    match op {
+        Op::MATCH if equal => '=',
+        Op::MATCH => 'X',
+        _ => basic(op)
+    }
+}
+
+pub(crate) fn run_length(codes: impl Iterator<Item=char>) -> String {
+    let mut cigar = String::new();
+    let mut run: Option<(char, usize)> = None;
+    for code in codes.map(Some).chain(iter::once(None)) {
+        run = match (run, code) {
+            (Some((current, count)), Some(code)) if current == code => Some((current, count + 1)),
+            (previous, code) => {
+                if let Some((current, count)) = previous {
+                    cigar.push_str(&format!("{}{}", count, current));
+                }
+                code.map(|code| (code, 1))
+            }
+        }
+    }
+    cigar
+}
+
+/// Splits a CIGAR into its operations as (offset, count, code).
+fn parse(cigar: &str) -> Result<Vec<(usize, usize, char)>, CigarParseError> {
+    let mut operations = Vec::new();
+    let mut start = 0;
+    for (offset, c) in cigar.char_indices() {
+        if c.is_ascii_digit() {
+            continue;
+        }
+        let count: usize = cigar[start..offset].parse()
+            .map_err(|_| parse_error(start, &format!("missing count before {}", c)))?;
+        if count == 0 {
+            return Err(parse_error(start, "zero-length operation"));
+        }
+        operations.push((start, count, c));
+        start = offset + c.len_utf8();
+    }
+    if start < cigar.len() {
+        return Err(parse_error(start, "count without operation"));
+    }
+    Ok(operations)
+}
+
+fn parse_error(offset: usize, message: &str) -> CigarParseError {
+    CigarParseError { offset, message: message.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::Aligner;
+    use crate::alignment::Alignment;
+    use crate::cigar::CigarParseError;
+    use crate::nt_aligner::{GlobalNtAligner, LocalNtAligner, NtAlignmentConfig};
+
+    const CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -2.0,
+        subject_gap_extension_penalty: -0.5,
+        reference_gap_penalty: -2.0,
+        reference_gap_extension_penalty: -0.5,
+    };
+
+    #[test]
+    fn test_to_cigar() {
+        let alignment = Alignment::from("ACGT__TTA", "AGGTCCT_A", 0.0);
+        assert_eq!(alignment.to_cigar(), "4M2D1M1I1M");
+        assert_eq!(alignment.to_extended_cigar(7), "1=1X2=2D1=1I1=");
+    }
+
+    #[test]
+    fn test_soft_clipping() {
+        let alignment = Alignment::starting_at((2, 3), "GCA_TG", "GCAGTG", 4.0);
+        assert_eq!(alignment.to_cigar(), "3M1D2M");
+        assert_eq!(alignment.to_extended_cigar(10), "2S3=1D2=3S");
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(Alignment::from("", "", 0.0).to_cigar(), "");
+        assert_eq!(Alignment::from("", "", 0.0).to_extended_cigar(3), "3S");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let global = GlobalNtAligner::from(CONFIG);
+        let local = LocalNtAligner::from(CONFIG);
+        let pairs: [(&[u8], &[u8]); 4] = [
+            (b"ACGTAGCTAGCT", b"AGCAGCTAGCT"),
+            (b"CGGTTAG", b"TTGC"),
+            (b"TTTTGCATGCAAAA", b"CCGCATGCCC"),
+            (b"GCATGCATCCCGCATGCAT", b"AAGCATGCATGCATGCATAA"),
+        ];
+        for (subject, reference) in pairs.iter() {
+            for alignment in [global.align(subject, reference).unwrap(), local.align(subject, reference).unwrap()].iter() {
+                assert_eq!(
+                    Alignment::from_cigar(&alignment.to_cigar(), subject, reference, alignment.start(), alignment.score).as_ref(),
+                    Ok(alignment)
+                );
+                assert_eq!(
+                    Alignment::from_cigar(&alignment.to_extended_cigar(subject.len()), subject, reference, alignment.start(), alignment.score).as_ref(),
+                    Ok(alignment)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_mixed_case() {
+        let alignment = Alignment::from("acGT_a", "ACgTTc", 0.0);
+        let cigar = alignment.to_extended_cigar(5);
+        assert_eq!(cigar, "4=1D1X");
+        assert_eq!(Alignment::from_cigar(&cigar, b"acGTa", b"ACgTTc", (0, 0), 0.0), Ok(alignment));
+    }
+
+    #[test]
+    fn test_from_cigar_anchors() {
+        assert_eq!(
+            Alignment::from_cigar("1S2M1I1D1M", b"TACGTA", b"GGACTA", (1, 2), 1.0),
+            Ok(Alignment::starting_at((1, 2), "ACG_T", "AC_TA", 1.0))
+        );
+    }
+
+    #[test]
+    fn test_from_cigar_errors() {
+        let error = |offset: usize, message: &str| Err(CigarParseError { offset, message: message.to_string() });
+        assert_eq!(Alignment::from_cigar("3M2Q", b"ACGTT", b"ACGTT", (0, 0), 0.0), error(2, "unsupported operation Q"));
+        assert_eq!(Alignment::from_cigar("3MM", b"ACG", b"ACG", (0, 0), 0.0), error(2, "missing count before M"));
+        assert_eq!(Alignment::from_cigar("0M", b"ACG", b"ACG", (0, 0), 0.0), error(0, "zero-length operation"));
+        assert_eq!(Alignment::from_cigar("3M2", b"ACG", b"ACG", (0, 0), 0.0), error(2, "count without operation"));
+        assert_eq!(Alignment::from_cigar("4M", b"ACG", b"ACGT", (0, 0), 0.0), error(0, "alignment runs past the sequence ends"));
+        assert_eq!(Alignment::from_cigar("1M1S1M", b"ACG", b"ACG", (0, 0), 0.0), error(2, "soft clip inside the alignment"));
+        assert_eq!(Alignment::from_cigar("2M", b"ACG", b"ACG", (0, 0), 0.0).map(|a| a.to_cigar()), Ok("2M".to_string()));
+        assert_eq!(Alignment::from_cigar("2S1M", b"ACG", b"ACG", (1, 0), 0.0), error(0, "soft clip does not reach the alignment start"));
+        assert_eq!(Alignment::from_cigar("2M2S", b"ACG", b"ACG", (0, 0), 0.0), error(2, "soft clip does not reach the subject end"));
+        assert_eq!(Alignment::from_cigar("2=", b"ACG", b"AGG", (0, 0), 0.0), error(0, "= at subject position 2"));
+        assert_eq!(Alignment::from_cigar("1M", b"ACG", b"AGG", (0, 4), 0.0), error(0, "alignment starts past the sequence ends"));
+    }
+}
@@ -1,7 +1,54 @@
-use crate::element::FScore;
+use crate::alignment::same_symbol;
+use crate::element::Score;
+use crate::error::Result;
 
+/// Scores and penalties of an alignment. Positions are 1-based indices into the sequences:
+/// a substitution gets the `(subject, reference)` positions of its pair, a subject gap
+/// penalty the reference position it skips and a reference gap penalty the subject
+/// position it skips.
 pub trait AlignmentConfig {
-    fn get_substitution_score(&self, pos: (usize, usize), s: u8, r: u8) -> FScore;
-    fn get_subject_gap_opening_penalty(&self, pos: usize) -> FScore;
-    fn get_reference_gap_opening_penalty(&self, pos: usize) -> FScore;
+    type Score: Score;
+
+    fn get_substitution_score(&self, pos: (usize, usize), s: u8, r: u8) -> Self::Score;
+    fn get_subject_gap_opening_penalty(&self, pos: usize) -> Self::Score;
+    fn get_reference_gap_opening_penalty(&self, pos: usize) -> Self::Score;
+
+    fn get_subject_gap_extension_penalty(&self, pos: usize) -> Self::Score {
+        self.get_subject_gap_opening_penalty(pos)
+    }
+
+    fn get_reference_gap_extension_penalty(&self, pos: usize) -> Self::Score {
+        self.get_reference_gap_opening_penalty(pos)
+    }
+
+    /// Whether `symbol` may appear in the aligned sequences.
+    fn accepts(&self, _symbol: u8) -> bool {
+        true
+    }
+
+    /// Whether an aligned pair shows as a match in `Alignment::pairs_with`.
+    fn matches(&self, s: u8, r: u8) -> bool {
+        same_symbol(s, r)
+    }
+
+    /// Whether the scores and penalties are the same at every position, as kernels that
+    /// precompute them per symbol require.
+    fn is_position_independent(&self) -> bool {
+        false
+    }
+
+    /// The length of the one subject the scores are made for, as with base qualities.
+    fn subject_length(&self) -> Option<usize> {
+        None
+    }
+
+    /// The length of the one reference the scores are made for, as with a profile.
+    fn reference_length(&self) -> Option<usize> {
+        None
+    }
+
+    /// Rejects scores and penalties no alignment can be built with.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
 }
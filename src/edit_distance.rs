@@ -0,0 +1,284 @@
+use crate::alignment::{Alignment, AlignmentBuilder};
+use crate::element::{FScore, Op};
+use crate::error::{Error, Result, MAX_MATRIX_CELLS};
+use crate::matrix::{self, Idx};
+use std::cmp;
+
+const WORD: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// The whole pattern against the whole text.
+    Global,
+    /// The pattern against the stretch of the text it matches best; the rest of the text is free.
+    SemiGlobal,
+}
+
+/// Unit-cost edit distance by Myers' bit-vector algorithm, computing 64 rows of a column
+/// per machine word. Symbols are compared as bytes, and empty sequences are allowed.
+pub struct MyersAligner {
+    pub mode: Mode,
+}
+
+impl MyersAligner {
+    pub fn distance(&self, pattern: &[u8], text: &[u8]) -> usize {
+        let mut columns = Columns::new(pattern, self.mode);
+        let mut best = columns.score;
+        for &symbol in text {
+            columns.advance(symbol);
+            best = cmp::min(best, columns.score);
+        }
+        match self.mode {
+            Mode::Global => columns.score,
+            Mode::SemiGlobal => best
+        }
+    }
+
+    /// The alignment of `distance`, with the pattern as its subject and the text as its reference.
+    /// Its score is the negated distance.
+    pub fn align(&self, pattern: &[u8], text: &[u8]) -> Result<Alignment> {
+        let mut columns = Columns::new(pattern, self.mode);
+        let blocks = columns.pv.len();
+        if (text.len() + 1).checked_mul(blocks).is_none_or(|words| words > MAX_MATRIX_CELLS) {
+            return Err(Error::TooLarge { subject_length: pattern.len(), reference_length: text.len() });
+        }
+        let mut table = Table {
+            mode: self.mode,
+            blocks,
+            pv: Vec::with_capacity((text.len() + 1) * blocks),
+            mv: Vec::with_capacity((text.len() + 1) * blocks),
+        };
+        table.push(&columns);
+        let mut end = (pattern.len(), 0);
+        let mut best = columns.score;
+        for (j, &symbol) in text.iter().enumerate() {
+            columns.advance(symbol);
+            table.push(&columns);
+            if self.mode == Mode::Global || columns.score < best {
+                best = columns.score;
+                end.1 = j + 1;
+            }
+        }
+        let mut builder = AlignmentBuilder::new(pattern, text);
+        let mut idx = end;
+        let mut distance = best;
+        while idx.0 > 0 || (idx.1 > 0 && self.mode == Mode::Global) {
+            let (i, j) = idx;
+            let op = if i > 0 && j > 0 && table.value((i - 1, j - 1)) + (pattern[i - 1] != text[j - 1]) as usize == distance {
+                Op::MATCH
+            } else if i > 0 && table.value((i - 1, j)) + 1 == distance {
+                Op::INSERT
+            } else {
+                Op::DELETE
+            };
+            builder.take(op, idx)?;
+            idx = matrix::move_back(op, idx);
+            distance = table.value(idx);
+        }
+        builder.take(Op::START, idx)?;
+        Ok(builder.build(0.0 - best as FScore))
+    }
+}
+
+/// The current column as vertical deltas, bit `i` of `pv` (`mv`) being set when the distance
+/// grows (shrinks) by one from row `i` to row `i + 1`.
+struct Columns {
+    /// By symbol, the rows of the pattern holding it.
+    peq: Vec<u64>,
+    pv: Vec<u64>,
+    mv: Vec<u64>,
+    last_row: u64,
+    top_row_delta: isize,
+    /// The distance in the last row.
+    score: usize,
+}
+
+impl Columns {
+    fn new(pattern: &[u8], mode: Mode) -> Self {
+        let blocks = pattern.len().div_ceil(WORD);
+        let mut peq = vec![0; 256 * blocks];
+        for (i, &symbol) in pattern.iter().enumerate() {
+            peq[symbol as usize * blocks + i / WORD] |= 1 << (i % WORD);
+        }
+        Columns {
+            peq,
+            pv: vec![!0; blocks],
+            mv: vec![0; blocks],
+            last_row: 1 << ((pattern.len() + WORD - 1) % WORD),
+            top_row_delta: if mode == Mode::Global { 1 } else { 0 },
+            score: pattern.len(),
+        }
+    }
+
+    fn advance(&mut self, symbol: u8) {
+        let blocks = self.pv.len();
+        let eq = &self.peq[symbol as usize * blocks..(symbol as usize + 1) * blocks];
+        let mut carry = self.top_row_delta;
+        for (block, ((pv, mv), &eq)) in self.pv.iter_mut().zip(self.mv.iter_mut()).zip(eq).enumerate() {
+            let high = if block + 1 == blocks { self.last_row } else { 1 << (WORD - 1) };
+            carry = advance_block(pv, mv, eq, carry, high);
+        }
+        self.score = (self.score as isize + carry) as usize;
+    }
+}
+
+/// Moves one block of a column a text symbol forward, given the horizontal delta entering
+/// its top row, and returns the one leaving its `high` row.
+fn advance_block(pv: &mut u64, mv: &mut u64, eq: u64, carry: isize, high: u64) -> isize {
+    let xv = eq | *mv;
+    let eq = if carry < 0 { eq | 1 } else { eq };
+    let xh = ((eq & *pv).wrapping_add(*pv) ^ *pv) | eq;
+    let mut ph = *mv | !(xh | *pv);
+    let mut mh = *pv & xh;
+    let out = if ph & high != 0 {
+        1
+    } else if mh & high != 0 {
+        -1
+    } else {
+        0
+    };
+    ph <<= 1;
+    mh <<= 1;
+    if carry < 0 {
+        mh |= 1;
+    } else if carry > 0 {
+        ph |= 1;
+    }
+    *pv = mh | !(xv | ph);
+    *mv = ph & xv;
+    out
+}
+
+/// The vertical deltas of every column, from which the traceback reads distances.
+struct Table {
+    mode: Mode,
+    blocks: usize,
+    pv: Vec<u64>,
+    mv: Vec<u64>,
+}
+
+impl Table {
+    fn push(&mut self, columns: &Columns) {
+        self.pv.extend_from_slice(&columns.pv);
+        self.mv.extend_from_slice(&columns.mv);
+    }
+
+    fn value(&self, (row, col): Idx) -> usize {
+        let column = col * self.blocks;
+        let bits = |vectors: &[u64]| -> usize {
+            let full: u32 = vectors[column..column + row / WORD].iter().map(|v| v.count_ones()).sum();
+            let partial = match row % WORD {
+                0 => 0,
+                rest => (vectors[column + row / WORD] & ((1 << rest) - 1)).count_ones(),
+            };
+            (full + partial) as usize
+        };
+        let top = if self.mode == Mode::Global { col } else { 0 };
+        top + bits(&self.pv) - bits(&self.mv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::{Aligner, FreeEndGaps, GlobalAligner};
+    use crate::alignment::Alignment;
+    use crate::edit_distance::{Mode, MyersAligner};
+    use crate::element::Op;
+    use crate::nt_aligner::NtAlignmentConfig;
+    use crate::xorshift::XorShift;
+
+    const GLOBAL: MyersAligner = MyersAligner { mode: Mode::Global };
+    const SEMI_GLOBAL: MyersAligner = MyersAligner { mode: Mode::SemiGlobal };
+
+    const UNIT_COST: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 0.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -1.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -1.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    fn sequences(count: usize, max_length: u64) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut random = XorShift::new(0x2545_f491_4f6c_dd1d);
+        let mut next = |bound: u64| random.below(bound);
+        (0..count)
+            .map(|_| {
+                let pattern: Vec<u8> = (0..=next(max_length)).map(|_| b"ACGT"[next(4)]).collect();
+                let mut text: Vec<u8> = (0..next(8)).map(|_| b"ACGT"[next(4)]).collect();
+                for &c in pattern.iter() {
+                    match next(12) {
+                        0 => {}
+                        1 => text.extend_from_slice(&[c, b"ACGT"[next(4)]]),
+                        2 => text.push(b"ACGT"[next(4)]),
+                        _ => text.push(c)
+                    }
+                }
+                text.extend((0..next(8)).map(|_| b"ACGT"[next(4)]));
+                (pattern, text)
+            })
+            .collect()
+    }
+
+    fn edits(alignment: &Alignment) -> usize {
+        alignment.anchors.iter()
+            .filter(|a| a.op != Op::START && a.s != a.r)
+            .count()
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(GLOBAL.distance(b"kitten", b"sitting"), 3);
+        assert_eq!(GLOBAL.distance(b"ACGT", b"ACGT"), 0);
+        assert_eq!(GLOBAL.distance(b"ACGT", b"TTACGTTT"), 4);
+        assert_eq!(SEMI_GLOBAL.distance(b"ACGT", b"TTACGTTT"), 0);
+        assert_eq!(SEMI_GLOBAL.distance(b"ACGT", b"TTACTTT"), 1);
+        assert_eq!(GLOBAL.distance(b"", b"ACG"), 3);
+        assert_eq!(SEMI_GLOBAL.distance(b"", b"ACG"), 0);
+        assert_eq!(SEMI_GLOBAL.distance(b"ACG", b""), 3);
+    }
+
+    #[test]
+    fn test_same_as_dp() {
+        let global = GlobalAligner::from(UNIT_COST);
+        let semi_global = GlobalAligner { config: UNIT_COST, free_end_gaps: FreeEndGaps::SUBJECT_IN_REFERENCE };
+        for (pattern, text) in sequences(300, 200).iter() {
+            for (myers, dp) in [(&GLOBAL, &global), (&SEMI_GLOBAL, &semi_global)].iter() {
+                let distance = myers.distance(pattern, text);
+                assert_eq!(-(distance as f64), dp.score(pattern, text).unwrap());
+                let alignment = myers.align(pattern, text).unwrap();
+                assert_eq!(alignment.score, -(distance as f64));
+                assert_eq!(edits(&alignment), distance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_align() {
+        assert_eq!(
+            GLOBAL.align(b"kitten", b"sitting"),
+            Ok(Alignment::from("kitten_", "sitting", -3.0))
+        );
+        assert_eq!(
+            SEMI_GLOBAL.align(b"GATTACA", b"CCGATTCACC"),
+            Ok(Alignment::starting_at((0, 2), "GATTACA", "GATT_CA", -1.0))
+        );
+        assert_eq!(SEMI_GLOBAL.align(b"", b"ACG"), Ok(Alignment::from("", "", 0.0)));
+    }
+
+    #[test]
+    fn test_long_pattern() {
+        let barcode = b"ACGTTGCAAGTCCGATGCATTGCA".repeat(8);
+        let mut read = b"TTTTT".to_vec();
+        read.extend_from_slice(&barcode);
+        read[40] = b'G';
+        read[150] = b'A';
+        read.remove(100);
+        read.extend_from_slice(b"GGGGG");
+        assert_eq!(SEMI_GLOBAL.distance(&barcode, &read), 3);
+        let alignment = SEMI_GLOBAL.align(&barcode, &read).unwrap();
+        assert_eq!(alignment.start(), (0, 5));
+        assert_eq!(alignment.end(), (barcode.len(), read.len() - 5));
+        assert_eq!(edits(&alignment), 3);
+    }
+}
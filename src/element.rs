@@ -1,7 +1,86 @@
+use std::fmt::Debug;
 use std::ops::Add;
 
 pub type FScore = f64;
 
+/// The score type of the dynamic programming cells. Integer scores are exact and make for
+/// smaller matrices, floating point ones allow fractional scores.
+pub trait Score: Copy + Debug + Default + PartialOrd + Add<Output=Self> {
+    const ZERO: Self;
+    /// Scores no path reaches, below every other score.
+    const UNREACHABLE: Self;
+
+    /// Sum that stays `UNREACHABLE` when either side is. An integer sum out of range becomes
+    /// the greatest score and stays so, so that it reaches the end of every alignment it
+    /// takes part in and `overflowed` tells there.
+    fn plus(self, rhs: Self) -> Self;
+
+    fn to_f64(self) -> FScore;
+
+    fn overflowed(self) -> bool {
+        false
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other > self { other } else { self }
+    }
+
+    fn is_nan(self) -> bool {
+        self.partial_cmp(&self).is_none()
+    }
+}
+
+macro_rules! integer_score {
+    ($($t:ty),*) => {$(
+        impl Score for $t {
+            const ZERO: Self = 0;
+            const UNREACHABLE: Self = <$t>::MIN;
+
+            fn plus(self, rhs: Self) -> Self {
+                if self == Self::UNREACHABLE || rhs == Self::UNREACHABLE {
+                    Self::UNREACHABLE
+                } else if self.overflowed() || rhs.overflowed() {
+                    Self::MAX
+                } else {
+                    match self.checked_add(rhs) {
+                        Some(sum) if sum != Self::UNREACHABLE => sum,
+                        _ => Self::MAX
+                    }
+                }
+            }
+
+            fn overflowed(self) -> bool {
+                self == Self::MAX
+            }
+
+            fn to_f64(self) -> FScore {
+                FScore::from(self)
+            }
+        }
+    )*}
+}
+
+macro_rules! float_score {
+    ($($t:ty),*) => {$(
+        impl Score for $t {
+            const ZERO: Self = 0.0;
+            const UNREACHABLE: Self = <$t>::NEG_INFINITY;
+
+            fn plus(self, rhs: Self) -> Self {
+                self + rhs
+            }
+
+            fn to_f64(self) -> FScore {
+                FScore::from(self)
+            }
+        }
+    )*}
+}
+
+integer_score!(i16, i32);
+float_score!(f32, f64);
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Op {
     START,
@@ -11,22 +90,48 @@ pub enum Op {
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
-pub struct Element {
+pub struct Element<S = FScore> {
     pub op: Op,
-    pub score: FScore,
+    pub score: S,
+    pub extends_insertion: bool,
+    pub extends_deletion: bool,
 }
 
-impl Add<FScore> for Element {
-    type Output = FScore;
+impl<S: Score> Add<S> for Element<S> {
+    type Output = S;
 
-    fn add(self, rhs: FScore) -> Self::Output {
-        self.score + rhs
+    fn add(self, rhs: S) -> Self::Output {
+        self.score.plus(rhs)
     }
 }
 
-impl Default for Element {
+impl<S: Score> Default for Element<S> {
     fn default() -> Self {
-        Element { op: Op::START, score: 0.0 }
+        Element { op: Op::START, score: S::ZERO, extends_insertion: false, extends_deletion: false }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::element::{Element, Score};
+    use std::mem::size_of;
+
+    #[test]
+    fn test_plus() {
+        assert_eq!(3i32.plus(-5), -2);
+        assert_eq!(i32::UNREACHABLE.plus(7), i32::UNREACHABLE);
+        assert_eq!((-30_000i16).plus(-10_000), i16::MAX);
+        assert!((30_000i16).plus(10_000).overflowed());
+        assert!(i16::MAX.plus(-10_000).overflowed());
+        assert!(!(-30_000i16).plus(-2_000).overflowed());
+        assert!(!f64::MAX.plus(f64::MAX).overflowed());
+        assert_eq!(f64::UNREACHABLE.plus(7.0), f64::UNREACHABLE);
+    }
+
+    #[test]
+    fn test_element_size() {
+        assert_eq!(size_of::<Element<f64>>(), 16);
+        assert_eq!(size_of::<Element<i32>>(), 8);
+        assert_eq!(size_of::<Element<i16>>(), 6);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::element::{FScore, Score};
+use crate::matrix::Idx;
+use std::fmt;
+
+/// Full score matrices beyond this many cells are refused; `align_in_linear_space`
+/// and `score` handle such pairs without one.
+pub const MAX_MATRIX_CELLS: usize = 1 << 28;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A symbol the scoring configuration has no use for, at a 0-based position.
+    InvalidSymbol { sequence: Sequence, position: usize, symbol: u8 },
+    /// A NaN score, a positive penalty, or scoring the aligner cannot apply.
+    InvalidConfig { parameter: &'static str, value: FScore },
+    EmptySequence(Sequence),
+    TooLarge { subject_length: usize, reference_length: usize },
+    /// A matrix index outside the aligned sequences.
+    OutOfBounds(Idx),
+    /// The band of a banded alignment leaves out its first or last cell.
+    OutsideBand,
+    /// A sequence of another length than the one the scoring configuration is made for.
+    SequenceLength { sequence: Sequence, expected: usize, found: usize },
+    /// A score out of the range of the integer score type.
+    ScoreOverflow,
+    /// Every alignment of the sequences takes a forbidden step, such as a subject gap in a masked region.
+    NoAlignment,
+    /// A FASTQ quality character below `!` or above `~`, at a 0-based position.
+    InvalidQuality { position: usize, quality: u8 },
+    /// Both strands of a subject to align with scoring made for the one subject, as with base qualities.
+    SingleStrandScoring,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sequence {
+    Subject,
+    Reference,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sequence::Subject => write!(f, "subject"),
+            Sequence::Reference => write!(f, "reference")
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidSymbol { sequence, position, symbol } =>
+                write!(f, "invalid symbol {:?} at {} position {}", *symbol as char, sequence, position + 1),
+            Error::InvalidConfig { parameter, value } =>
+                write!(f, "invalid {}: {}", parameter, value),
+            Error::EmptySequence(sequence) =>
+                write!(f, "empty {}", sequence),
+            Error::TooLarge { subject_length, reference_length } =>
+                write!(
+                    f,
+                    "subject of length {} and reference of length {} exceed {} matrix cells",
+                    subject_length, reference_length, MAX_MATRIX_CELLS
+                ),
+            Error::OutOfBounds(idx) =>
+                write!(f, "index {:?} is outside the aligned sequences", idx),
+            Error::OutsideBand =>
+                write!(f, "the band does not contain both ends of the alignment"),
+            Error::SequenceLength { sequence, expected, found } =>
+                write!(f, "{} of length {} where the scoring expects {}", sequence, found, expected),
+            Error::ScoreOverflow =>
+                write!(f, "the alignment score overflows the score type"),
+            Error::NoAlignment =>
+                write!(f, "no alignment of the sequences is allowed by the scoring"),
+            Error::InvalidQuality { position, quality } =>
+                write!(f, "invalid quality {:?} at position {}", *quality as char, position + 1),
+            Error::SingleStrandScoring =>
+                write!(f, "the scoring is made for one strand of the subject"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// NaN scores are rejected.
+pub fn check_score<S: Score>(parameter: &'static str, value: S) -> Result<()> {
+    if value.is_nan() {
+        Err(Error::InvalidConfig { parameter, value: value.to_f64() })
+    } else {
+        Ok(())
+    }
+}
+
+/// The score an alignment ends with, refusing unreachable ends and scores that overflowed
+/// rather than reporting them clamped.
+pub fn check_end_score<S: Score>(score: S) -> Result<FScore> {
+    if score == S::UNREACHABLE {
+        Err(Error::NoAlignment)
+    } else if score.overflowed() {
+        Err(Error::ScoreOverflow)
+    } else {
+        Ok(score.to_f64())
+    }
+}
+
+/// NaN and positive penalties are rejected.
+pub fn check_penalty<S: Score>(parameter: &'static str, value: S) -> Result<()> {
+    if value.is_nan() || value > S::ZERO {
+        Err(Error::InvalidConfig { parameter, value: value.to_f64() })
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that neither sequence is empty and that each only holds symbols `accepts` allows.
+pub fn check_sequences(subject: &[u8], reference: &[u8], accepts: impl Fn(u8) -> bool) -> Result<()> {
+    for (sequence, symbols) in [(Sequence::Subject, subject), (Sequence::Reference, reference)].iter() {
+        if symbols.is_empty() {
+            return Err(Error::EmptySequence(*sequence));
+        }
+        if let Some(position) = symbols.iter().position(|&symbol| !accepts(symbol)) {
+            return Err(Error::InvalidSymbol { sequence: *sequence, position, symbol: symbols[position] });
+        }
+    }
+    Ok(())
+}
+
+pub fn check_matrix_size(subject: &[u8], reference: &[u8]) -> Result<()> {
+    match (subject.len() + 1).checked_mul(reference.len() + 1) {
+        Some(cells) if cells <= MAX_MATRIX_CELLS => Ok(()),
+        _ => Err(Error::TooLarge { subject_length: subject.len(), reference_length: reference.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{Error, Sequence, check_matrix_size, check_penalty, check_sequences, MAX_MATRIX_CELLS};
+
+    #[test]
+    fn test_check_penalty() {
+        assert_eq!(check_penalty("gap penalty", -1.0), Ok(()));
+        assert_eq!(
+            check_penalty("gap penalty", 2.0),
+            Err(Error::InvalidConfig { parameter: "gap penalty", value: 2.0 })
+        );
+        assert!(check_penalty("gap penalty", f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_check_sequences() {
+        let nucleotide = |symbol: u8| b"ACGT".contains(&symbol);
+        assert_eq!(check_sequences(b"ACG", b"TTA", nucleotide), Ok(()));
+        assert_eq!(check_sequences(b"", b"TTA", nucleotide), Err(Error::EmptySequence(Sequence::Subject)));
+        assert_eq!(
+            check_sequences(b"ACG", b"TTXA", nucleotide),
+            Err(Error::InvalidSymbol { sequence: Sequence::Reference, position: 2, symbol: b'X' })
+        );
+    }
+
+    #[test]
+    fn test_check_matrix_size() {
+        assert_eq!(check_matrix_size(&[b'A'; 100], &[b'A'; 100]), Ok(()));
+        let long = vec![b'A'; MAX_MATRIX_CELLS / 2];
+        assert_eq!(
+            check_matrix_size(&long, b"ACG"),
+            Err(Error::TooLarge { subject_length: long.len(), reference_length: 3 })
+        );
+    }
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(
+            Error::InvalidSymbol { sequence: Sequence::Subject, position: 4, symbol: b'!' }.to_string(),
+            "invalid symbol '!' at subject position 5"
+        );
+        assert_eq!(
+            Error::InvalidConfig { parameter: "mismatch penalty", value: 1.0 }.to_string(),
+            "invalid mismatch penalty: 1"
+        );
+        assert_eq!(Error::EmptySequence(Sequence::Reference).to_string(), "empty reference");
+        assert_eq!(
+            Error::SequenceLength { sequence: Sequence::Reference, expected: 12, found: 10 }.to_string(),
+            "reference of length 10 where the scoring expects 12"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{self, BufRead};
+
+/// A FASTA or FASTQ record; only FASTQ records carry qualities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub name: String,
+    pub sequence: Vec<u8>,
+    pub quality: Option<Vec<u8>>,
+}
+
+impl AsRef<[u8]> for Record {
+    fn as_ref(&self) -> &[u8] {
+        &self.sequence
+    }
+}
+
+/// Reads FASTA and FASTQ records, telling the two apart by each record's first character.
+/// FASTA sequences may span several lines, FASTQ sequences and qualities one line each.
+pub struct Records<R> {
+    reader: R,
+    header: Option<String>,
+    line: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R) -> Self {
+        Records { reader, header: None, line: 0 }
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        Ok(Some(line.trim_end().to_string()))
+    }
+
+    fn next_header(&mut self) -> io::Result<Option<String>> {
+        if let Some(header) = self.header.take() {
+            return Ok(Some(header));
+        }
+        while let Some(line) = self.next_line()? {
+            if !line.is_empty() {
+                return Ok(Some(line));
+            }
+        }
+        Ok(None)
+    }
+
+    fn fasta(&mut self, name: String) -> io::Result<Record> {
+        let mut sequence = Vec::new();
+        while let Some(line) = self.next_line()? {
+            if line.starts_with('>') || line.starts_with('@') {
+                self.header = Some(line);
+                break;
+            }
+            sequence.extend(line.bytes().filter(|b| !b.is_ascii_whitespace()));
+        }
+        Ok(Record { name, sequence, quality: None })
+    }
+
+    fn fastq(&mut self, name: String) -> io::Result<Record> {
+        let sequence = self.required_line()?.into_bytes();
+        if !self.required_line()?.starts_with('+') {
+            return Err(self.error("expected '+' separator"));
+        }
+        let quality = self.required_line()?.into_bytes();
+        if quality.len() != sequence.len() {
+            return Err(self.error("quality and sequence lengths differ"));
+        }
+        Ok(Record { name, sequence, quality: Some(quality) })
+    }
+
+    fn required_line(&mut self) -> io::Result<String> {
+        match self.next_line()? {
+            Some(line) => Ok(line),
+            None => Err(self.error("truncated FASTQ record"))
+        }
+    }
+
+    fn error(&self, message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", self.line, message))
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = match self.next_header() {
+            Ok(Some(header)) => header,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e))
+        };
+        let name = header.get(1..)
+            .and_then(|title| title.split_whitespace().next())
+            .unwrap_or_default()
+            .to_string();
+        Some(match header.chars().next() {
+            Some('>') => self.fasta(name),
+            Some('@') => self.fastq(name),
+            _ => Err(self.error("expected a '>' or '@' header"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fastx::{Record, Records};
+    use std::io;
+
+    fn records(text: &str) -> io::Result<Vec<Record>> {
+        Records::new(text.as_bytes()).collect()
+    }
+
+    fn fasta(name: &str, sequence: &str) -> Record {
+        Record { name: name.to_string(), sequence: sequence.as_bytes().to_vec(), quality: None }
+    }
+
+    #[test]
+    fn test_fasta() {
+        assert_eq!(
+            records(">seq1 first sequence\nACGT\nTTGA\n\n>seq2\r\nGGC\r\n").unwrap(),
+            vec![fasta("seq1", "ACGTTTGA"), fasta("seq2", "GGC")]
+        );
+    }
+
+    #[test]
+    fn test_fastq() {
+        assert_eq!(
+            records("@read1 lane 1\nACGT\n+\nII#5\n@read2\nGG\n+read2\n!!\n").unwrap(),
+            vec![
+                Record { name: "read1".to_string(), sequence: b"ACGT".to_vec(), quality: Some(b"II#5".to_vec()) },
+                Record { name: "read2".to_string(), sequence: b"GG".to_vec(), quality: Some(b"!!".to_vec()) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_input() {
+        assert_eq!(records("").unwrap(), vec![]);
+        assert_eq!(records(">empty\n").unwrap(), vec![fasta("empty", "")]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let message = |text: &str| records(text).unwrap_err().to_string();
+        assert_eq!(message("ACGT\n"), "line 1: expected a '>' or '@' header");
+        assert_eq!(message("@read\nACGT\n+\nII\n"), "line 4: quality and sequence lengths differ");
+        assert_eq!(message("@read\nACGT\nIIII\n"), "line 3: expected '+' separator");
+        assert_eq!(message("@read\nACGT\n"), "line 2: truncated FASTQ record");
+    }
+}
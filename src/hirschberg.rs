@@ -0,0 +1,549 @@
+use crate::aligner::{FreeEndGaps, gap, select, floor};
+use crate::alignment::{Alignment, AlignmentBuilder};
+use crate::config::AlignmentConfig;
+use crate::element::{Op, Score};
+use crate::error::{Result, check_end_score};
+use crate::matrix::{self, Idx};
+
+const OPS: [Op; 3] = [Op::MATCH, Op::INSERT, Op::DELETE];
+
+/// One value per kind of move into (or out of) a cell, as in the three Gotoh matrices.
+#[derive(Debug, Clone, Copy)]
+struct ByOp<T> {
+    substitution: T,
+    insertion: T,
+    deletion: T,
+}
+
+type Scores<S> = ByOp<S>;
+
+impl<T: Copy> ByOp<T> {
+    fn all(value: T) -> Self {
+        ByOp { substitution: value, insertion: value, deletion: value }
+    }
+
+    fn of(&self, op: Op) -> T {
+        match op {
+            Op::INSERT => self.insertion,
+            Op::DELETE => self.deletion,
+            _ => self.substitution
+        }
+    }
+}
+
+impl<S: Score> Scores<S> {
+    fn unreachable() -> Self {
+        ByOp::all(S::UNREACHABLE)
+    }
+
+    fn only(op: Op) -> Self {
+        let mut scores = Self::unreachable();
+        match op {
+            Op::INSERT => scores.insertion = S::ZERO,
+            Op::DELETE => scores.deletion = S::ZERO,
+            _ => scores.substitution = S::ZERO
+        }
+        scores
+    }
+
+    fn best(&self) -> (S, Op) {
+        let element = select(self.substitution, self.insertion, self.deletion);
+        (element.score, element.op)
+    }
+}
+
+/// Global alignment in memory linear in the sequence lengths. Rows are split in half, the
+/// crossing of the optimal path with the middle row is located from a forward and a backward
+/// pass, and both halves are solved recursively. Every pass tracks the last move into a cell,
+/// so gaps spanning the middle row are charged as a single gap.
+pub fn align_global<C: AlignmentConfig>(
+    config: &C,
+    free_end_gaps: FreeEndGaps,
+    subject: &[u8],
+    reference: &[u8],
+) -> Result<Alignment> {
+    Grid { config, subject, reference, free_end_gaps }
+        .align((0, 0), (subject.len(), reference.len()))
+}
+
+/// Local alignment in linear memory: one pass finds the best cell together with the cell its
+/// path starts from, and the span between them is aligned globally.
+pub fn align_local<C: AlignmentConfig>(config: &C, subject: &[u8], reference: &[u8]) -> Result<Alignment> {
+    let (start, end) = local_bounds(config, subject, reference);
+    Grid { config, subject, reference, free_end_gaps: FreeEndGaps::NONE }
+        .align(start, end)
+}
+
+struct Grid<'a, C> {
+    config: &'a C,
+    subject: &'a [u8],
+    reference: &'a [u8],
+    free_end_gaps: FreeEndGaps,
+}
+
+impl<'a, C: AlignmentConfig> Grid<'a, C> {
+    fn align(&self, start: Idx, end: Idx) -> Result<Alignment> {
+        let mut moves = Vec::with_capacity(end.0 - start.0 + end.1 - start.1);
+        self.solve(start, end, Op::MATCH, None, &mut moves);
+        let mut score = C::Score::ZERO;
+        let mut cursor = start;
+        let mut previous = Op::MATCH;
+        let mut anchors = Vec::with_capacity(moves.len());
+        for op in moves {
+            cursor = matrix::move_forward(op, cursor);
+            score = score.plus(self.cost(op, cursor, previous));
+            previous = op;
+            anchors.push((op, cursor));
+        }
+        let mut builder = AlignmentBuilder::new(self.subject, self.reference);
+        for (op, idx) in anchors.into_iter().rev() {
+            builder.take(op, idx)?;
+        }
+        builder.take(Op::START, start)?;
+        Ok(builder.build(check_end_score(score)?))
+    }
+
+    /// Appends the moves of the best path from `from` to `to`, given the move into `from`
+    /// and, optionally, the move the path has to end with.
+    fn solve(&self, from: Idx, to: Idx, entry: Op, exit: Option<Op>, moves: &mut Vec<Op>) {
+        if to.0 - from.0 < 2 {
+            return self.solve_directly(from, to, entry, exit, moves);
+        }
+        let middle = (from.0 + to.0) / 2;
+        let forward = self.forward(from, (middle, to.1), entry);
+        let backward = self.backward((middle, from.1), to, exit);
+        let mut crossing = (C::Score::UNREACHABLE, from.1, Op::MATCH);
+        for (offset, (head, tail)) in forward.iter().zip(backward.iter()).enumerate() {
+            for &op in OPS.iter() {
+                let score = head.of(op).plus(tail.of(op));
+                if score > crossing.0 {
+                    crossing = (score, from.1 + offset, op);
+                }
+            }
+        }
+        let (_, col, op) = crossing;
+        self.solve(from, (middle, col), entry, Some(op), moves);
+        self.solve((middle, col), to, op, exit, moves);
+    }
+
+    /// Best scores of paths from `from` into each cell of the last row.
+    fn forward(&self, from: Idx, to: Idx, entry: Op) -> Vec<Scores<C::Score>> {
+        let width = to.1 - from.1 + 1;
+        let mut row = vec![Scores::unreachable(); width];
+        let mut next = vec![Scores::unreachable(); width];
+        row[0] = Scores::only(entry);
+        for c in 1..width {
+            row[c].deletion = self.deleted(&row[c - 1], (from.0, from.1 + c));
+        }
+        for r in from.0 + 1..=to.0 {
+            next[0] = ByOp { insertion: self.inserted(&row[0], (r, from.1)), ..Scores::unreachable() };
+            for c in 1..width {
+                let idx = (r, from.1 + c);
+                next[c] = ByOp {
+                    substitution: row[c - 1].best().0.plus(self.substitution(idx)),
+                    insertion: self.inserted(&row[c], idx),
+                    deletion: self.deleted(&next[c - 1], idx),
+                };
+            }
+            std::mem::swap(&mut row, &mut next);
+        }
+        row
+    }
+
+    /// Best scores of paths from each cell of the first row to `to`, by the move into the cell.
+    fn backward(&self, from: Idx, to: Idx, exit: Option<Op>) -> Vec<Scores<C::Score>> {
+        let width = to.1 - from.1 + 1;
+        let mut row = vec![Scores::unreachable(); width];
+        let mut next = vec![Scores::unreachable(); width];
+        row[width - 1] = exit.map_or(ByOp::all(C::Score::ZERO), Scores::only);
+        for c in (0..width - 1).rev() {
+            row[c] = self.leave((to.0, from.1 + c), None, None, Some(&row[c + 1]));
+        }
+        for r in (from.0..to.0).rev() {
+            next[width - 1] = self.leave((r, to.1), Some(&row[width - 1]), None, None);
+            for c in (0..width - 1).rev() {
+                next[c] = self.leave((r, from.1 + c), Some(&row[c]), Some(&row[c + 1]), Some(&next[c + 1]));
+            }
+            std::mem::swap(&mut row, &mut next);
+        }
+        row
+    }
+
+    /// Full three-state fill with traceback, used once the rows are too few to split.
+    fn solve_directly(&self, from: Idx, to: Idx, entry: Op, exit: Option<Op>, moves: &mut Vec<Op>) {
+        let (height, width) = (to.0 - from.0 + 1, to.1 - from.1 + 1);
+        let unset: (Scores<C::Score>, ByOp<Op>) = (Scores::unreachable(), ByOp::all(Op::START));
+        let mut cells = vec![unset; height * width];
+        cells[0].0 = Scores::only(entry);
+        for r in 0..height {
+            for c in 0..width {
+                if r == 0 && c == 0 {
+                    continue;
+                }
+                let idx = (from.0 + r, from.1 + c);
+                let (mut scores, mut sources) = (Scores::unreachable(), ByOp::all(Op::START));
+                if r > 0 && c > 0 {
+                    let (score, op) = cells[(r - 1) * width + c - 1].0.best();
+                    scores.substitution = score.plus(self.substitution(idx));
+                    sources.substitution = op;
+                }
+                if r > 0 {
+                    let above = cells[(r - 1) * width + c].0;
+                    let (score, op) = above.best();
+                    let (score, extends) = gap(
+                        score.plus(self.insertion(idx, false)),
+                        above.insertion.plus(self.insertion(idx, true)),
+                    );
+                    scores.insertion = score;
+                    sources.insertion = if extends { Op::INSERT } else { op };
+                }
+                if c > 0 {
+                    let left = cells[r * width + c - 1].0;
+                    let (score, op) = left.best();
+                    let (score, extends) = gap(
+                        score.plus(self.deletion(idx, false)),
+                        left.deletion.plus(self.deletion(idx, true)),
+                    );
+                    scores.deletion = score;
+                    sources.deletion = if extends { Op::DELETE } else { op };
+                }
+                cells[r * width + c] = (scores, sources);
+            }
+        }
+        let last = cells[height * width - 1].0;
+        let mut state = match exit {
+            Some(op) if last.of(op) > C::Score::UNREACHABLE => op,
+            _ => last.best().1
+        };
+        let mut path = Vec::with_capacity(height + width);
+        let mut cursor = (height - 1, width - 1);
+        while cursor != (0, 0) {
+            let op = match cursor {
+                (0, _) => Op::DELETE,
+                (_, 0) => Op::INSERT,
+                _ => state
+            };
+            path.push(op);
+            state = cells[cursor.0 * width + cursor.1].1.of(op);
+            cursor = matrix::move_back(op, cursor);
+        }
+        moves.extend(path.iter().rev());
+    }
+
+    fn inserted(&self, above: &Scores<C::Score>, idx: Idx) -> C::Score {
+        gap(
+            above.best().0.plus(self.insertion(idx, false)),
+            above.insertion.plus(self.insertion(idx, true)),
+        ).0
+    }
+
+    fn deleted(&self, left: &Scores<C::Score>, idx: Idx) -> C::Score {
+        gap(
+            left.best().0.plus(self.deletion(idx, false)),
+            left.deletion.plus(self.deletion(idx, true)),
+        ).0
+    }
+
+    /// Scores of leaving `idx`, by the move into it, given the scores of its lower,
+    /// lower right and right neighbours.
+    fn leave(
+        &self,
+        (row, col): Idx,
+        below: Option<&Scores<C::Score>>,
+        diagonal: Option<&Scores<C::Score>>,
+        right: Option<&Scores<C::Score>>,
+    ) -> Scores<C::Score> {
+        let mut scores = Scores::unreachable();
+        if let Some(diagonal) = diagonal {
+            let score = self.substitution((row + 1, col + 1)).plus(diagonal.substitution);
+            scores = ByOp::all(score);
+        }
+        if let Some(below) = below {
+            let opening = self.insertion((row + 1, col), false).plus(below.insertion);
+            let extension = self.insertion((row + 1, col), true).plus(below.insertion);
+            scores.substitution = scores.substitution.max(opening);
+            scores.insertion = scores.insertion.max(extension);
+            scores.deletion = scores.deletion.max(opening);
+        }
+        if let Some(right) = right {
+            let opening = self.deletion((row, col + 1), false).plus(right.deletion);
+            let extension = self.deletion((row, col + 1), true).plus(right.deletion);
+            scores.substitution = scores.substitution.max(opening);
+            scores.insertion = scores.insertion.max(opening);
+            scores.deletion = scores.deletion.max(extension);
+        }
+        scores
+    }
+
+    fn cost(&self, op: Op, idx: Idx, previous: Op) -> C::Score {
+        match op {
+            Op::INSERT => self.insertion(idx, previous == Op::INSERT),
+            Op::DELETE => self.deletion(idx, previous == Op::DELETE),
+            _ => self.substitution(idx)
+        }
+    }
+
+    fn substitution(&self, (row, col): Idx) -> C::Score {
+        self.config.get_substitution_score((row, col), self.subject[row - 1], self.reference[col - 1])
+    }
+
+    /// Cost of the vertical move into `(row, col)`. The left column is charged like
+    /// `GlobalAligner::fill_left_column`, every other column like `fill`.
+    fn insertion(&self, (row, col): Idx, extends: bool) -> C::Score {
+        if (col == 0 && self.free_end_gaps.reference_leading) ||
+            (col == self.reference.len() && self.free_end_gaps.reference_trailing) {
+            C::Score::ZERO
+        } else if col == 0 {
+            if extends {
+                self.config.get_reference_gap_extension_penalty(row)
+            } else {
+                self.config.get_reference_gap_opening_penalty(row)
+            }
+        } else {
+            let opening = self.config.get_reference_gap_opening_penalty(row);
+            if extends {
+                opening.max(self.config.get_reference_gap_extension_penalty(row))
+            } else {
+                opening
+            }
+        }
+    }
+
+    /// Cost of the horizontal move into `(row, col)`, the top row charged like `fill_top_row`.
+    fn deletion(&self, (row, col): Idx, extends: bool) -> C::Score {
+        if (row == 0 && self.free_end_gaps.subject_leading) ||
+            (row == self.subject.len() && self.free_end_gaps.subject_trailing) {
+            C::Score::ZERO
+        } else if row == 0 {
+            if extends {
+                self.config.get_subject_gap_extension_penalty(col)
+            } else {
+                self.config.get_subject_gap_opening_penalty(col)
+            }
+        } else {
+            let opening = self.config.get_subject_gap_opening_penalty(col);
+            if extends {
+                opening.max(self.config.get_subject_gap_extension_penalty(col))
+            } else {
+                opening
+            }
+        }
+    }
+}
+
+/// Runs the local fill one row at a time, carrying along with every score the cell its
+/// traceback would stop at, and returns the start and end of the best local alignment.
+fn local_bounds<C: AlignmentConfig>(config: &C, subject: &[u8], reference: &[u8]) -> (Idx, Idx) {
+    let cols = reference.len() + 1;
+    let zero = C::Score::ZERO;
+    let mut above: Vec<(C::Score, Idx)> = (0..cols).map(|col| (zero, (0, col))).collect();
+    let mut insertions = vec![(C::Score::UNREACHABLE, (0, 0)); cols];
+    let mut best = (zero, (0, 0), (0, 0));
+    for row in 1..=subject.len() {
+        let mut current = vec![(zero, (row, 0)); cols];
+        let mut deletion = (C::Score::UNREACHABLE, (row, 0));
+        for col in 1..cols {
+            let (insertion_score, extends_insertion) = gap(
+                above[col].0.plus(config.get_reference_gap_opening_penalty(row)),
+                insertions[col].0.plus(config.get_reference_gap_extension_penalty(row)),
+            );
+            insertions[col] = (insertion_score, if extends_insertion { insertions[col].1 } else { above[col].1 });
+            let (deletion_score, extends_deletion) = gap(
+                current[col - 1].0.plus(config.get_subject_gap_opening_penalty(col)),
+                deletion.0.plus(config.get_subject_gap_extension_penalty(col)),
+            );
+            deletion = (deletion_score, if extends_deletion { deletion.1 } else { current[col - 1].1 });
+            let element = floor(select(
+                above[col - 1].0.plus(config.get_substitution_score((row, col), subject[row - 1], reference[col - 1])),
+                insertion_score,
+                deletion_score,
+            ));
+            current[col] = match element.op {
+                Op::MATCH => (element.score, above[col - 1].1),
+                Op::INSERT => (element.score, insertions[col].1),
+                Op::DELETE => (element.score, deletion.1),
+                Op::START => (zero, (row, col))
+            };
+            if current[col].0 > best.0 {
+                best = (current[col].0, current[col].1, (row, col));
+            }
+        }
+        above = current;
+    }
+    (best.1, best.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aa_aligner::{AaAlignmentConfig, GlobalAaAligner};
+    use crate::aligner::{Aligner, FreeEndGaps, GlobalAligner, LocalAligner};
+    use crate::alignment::{Alignment, GAP};
+    use crate::config::AlignmentConfig;
+    use crate::element::FScore;
+    use crate::nt_aligner::{GlobalNtAligner, LocalNtAligner, NtAlignmentConfig};
+    use crate::xorshift::XorShift;
+
+    const AFFINE_CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 2.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -3.0,
+        subject_gap_extension_penalty: -0.5,
+        reference_gap_penalty: -2.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    const LINEAR_CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -1.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -1.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    /// `AFFINE_CONFIG` doubled, to make every score integral.
+    const INTEGER_CONFIG: NtAlignmentConfig<i32> = NtAlignmentConfig {
+        match_score: 4,
+        mismatch_penalty: -2,
+        subject_gap_penalty: -6,
+        subject_gap_extension_penalty: -1,
+        reference_gap_penalty: -4,
+        reference_gap_extension_penalty: -2,
+    };
+
+    /// Gaps cost more towards the sequence ends, to catch off-by-one positions.
+    struct PositionalConfig;
+
+    impl AlignmentConfig for PositionalConfig {
+        type Score = FScore;
+
+        fn get_substitution_score(&self, pos: (usize, usize), s: u8, r: u8) -> FScore {
+            if s == r { 1.0 + (pos.1 % 3) as FScore } else { -1.0 - (pos.0 % 2) as FScore }
+        }
+        fn get_subject_gap_opening_penalty(&self, pos: usize) -> FScore {
+            -2.0 - pos as FScore
+        }
+        fn get_reference_gap_opening_penalty(&self, pos: usize) -> FScore {
+            -3.0 - (pos % 4) as FScore
+        }
+        fn get_subject_gap_extension_penalty(&self, pos: usize) -> FScore {
+            -0.5 * (pos % 3) as FScore
+        }
+        fn get_reference_gap_extension_penalty(&self, pos: usize) -> FScore {
+            -1.0 - 0.5 * (pos % 2) as FScore
+        }
+    }
+
+    fn sequences(count: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut random = XorShift::new(0x2545_f491_4f6c_dd1d);
+        let mut next = |bound: u64| random.below(bound);
+        (0..count)
+            .map(|_| {
+                let subject: Vec<u8> = (0..=next(30)).map(|_| b"ACGT"[next(4)]).collect();
+                let reference: Vec<u8> = (0..=next(30)).map(|_| b"ACGT"[next(4)]).collect();
+                (subject, reference)
+            })
+            .collect()
+    }
+
+    fn ungapped(aligned: &str) -> Vec<u8> {
+        aligned.bytes().filter(|&c| c != GAP as u8).collect()
+    }
+
+    fn assert_same_score<C, A>(aligner: &A, subject: &[u8], reference: &[u8])
+        where C: AlignmentConfig, A: Aligner<C> {
+        let full = aligner.align(subject, reference).unwrap();
+        let linear = aligner.align_in_linear_space(subject, reference).unwrap();
+        assert_eq!(linear.score, full.score);
+        assert_eq!(linear.anchors.len() - 1, linear.pairs(' ').count());
+        let (s, _, r) = linear.aligned_sequences();
+        let (start, end) = (linear.start(), linear.end());
+        assert_eq!(ungapped(&s), &subject[start.0..end.0]);
+        assert_eq!(ungapped(&r), &reference[start.1..end.1]);
+    }
+
+    #[test]
+    fn test_global() {
+        let aligner = GlobalNtAligner::from(AFFINE_CONFIG);
+        sequences(300).iter()
+            .for_each(|(s, r)| assert_same_score(&aligner, s, r));
+    }
+
+    #[test]
+    fn test_global_linear_gaps() {
+        let aligner = GlobalNtAligner::from(LINEAR_CONFIG);
+        sequences(300).iter()
+            .for_each(|(s, r)| assert_same_score(&aligner, s, r));
+    }
+
+    #[test]
+    fn test_free_end_gaps() {
+        let free_end_gaps = [
+            FreeEndGaps::SUBJECT_IN_REFERENCE,
+            FreeEndGaps::REFERENCE_IN_SUBJECT,
+            FreeEndGaps::OVERLAP,
+            FreeEndGaps::of(true, false, false, true),
+        ];
+        for &free_end_gaps in free_end_gaps.iter() {
+            let aligner = GlobalNtAligner { config: AFFINE_CONFIG, free_end_gaps };
+            sequences(100).iter()
+                .for_each(|(s, r)| assert_same_score(&aligner, s, r));
+        }
+    }
+
+    #[test]
+    fn test_position_dependent_scores() {
+        let global = GlobalAligner::from(PositionalConfig);
+        let local = LocalAligner::from(PositionalConfig);
+        sequences(200).iter()
+            .for_each(|(s, r)| {
+                assert_same_score(&global, s, r);
+                assert_same_score(&local, s, r);
+            });
+    }
+
+    #[test]
+    fn test_local() {
+        let aligner = LocalNtAligner::from(AFFINE_CONFIG);
+        sequences(300).iter()
+            .for_each(|(s, r)| {
+                assert_same_score(&aligner, s, r);
+                let full = aligner.align(s, r).unwrap();
+                let linear = aligner.align_in_linear_space(s, r).unwrap();
+                assert_eq!((linear.start(), linear.end()), (full.start(), full.end()));
+            });
+    }
+
+    #[test]
+    fn test_integer_scores() {
+        let global = GlobalNtAligner::from(INTEGER_CONFIG);
+        let local = LocalNtAligner::from(INTEGER_CONFIG);
+        let affine = GlobalNtAligner::from(AFFINE_CONFIG);
+        sequences(200).iter()
+            .for_each(|(s, r)| {
+                assert_same_score(&global, s, r);
+                assert_same_score(&local, s, r);
+                assert_eq!(global.align_in_linear_space(s, r).unwrap().score, 2.0 * affine.align(s, r).unwrap().score);
+            });
+    }
+
+    #[test]
+    fn test_same_alignment() {
+        assert_eq!(
+            GlobalAaAligner::from(AaAlignmentConfig::blosum62()).align_in_linear_space(b"MKTAYIAKQR", b"MKTQR").unwrap(),
+            Alignment::from("MKTAYIAKQR", "MKT_____QR", 9.0)
+        );
+        assert_eq!(
+            LocalNtAligner::from(LINEAR_CONFIG).align_in_linear_space(b"TTTTGCATGCAAAA", b"CCGCATGCCC").unwrap(),
+            Alignment::starting_at((4, 2), "GCATGC", "GCATGC", 6.0)
+        );
+    }
+
+    #[test]
+    fn test_single_symbols() {
+        let aligner = GlobalNtAligner::from(AFFINE_CONFIG);
+        assert_eq!(aligner.align_in_linear_space(b"A", b"A").unwrap(), Alignment::from("A", "A", 2.0));
+        assert_eq!(aligner.align_in_linear_space(b"ACG", b"T").unwrap(), aligner.align(b"ACG", b"T").unwrap());
+        assert_eq!(aligner.align_in_linear_space(b"T", b"ACG").unwrap(), aligner.align(b"T", b"ACG").unwrap());
+    }
+}
@@ -1,15 +1,15 @@
+use crate::element::Score;
 use std::iter::{successors};
-use std::ops::Add;
 use ndarray::{Dimension};
 use ndarray::iter::IterMut;
 
 pub fn accumulate<S, V>(size: usize, supplier: S) -> impl Iterator<Item=V>
-    where V: Add<V, Output=V> + Default + Copy,
+    where V: Score,
           S: Fn(usize) -> V {
     let mut range = 0..size;
     successors(
-        Some(V::default()),
-        move |acc| range.next().map(|n| *acc + supplier(n)),
+        Some(V::ZERO),
+        move |acc| range.next().map(|n| acc.plus(supplier(n))),
     )
 }
 
@@ -0,0 +1,259 @@
+use crate::aligner::{GlobalAligner, LocalAligner};
+use crate::banded::BandedAligner;
+use crate::config::AlignmentConfig;
+use crate::element::FScore;
+use crate::error::{Result, check_penalty, check_score};
+use crate::strand::NucleotideConfig;
+
+const A: u8 = 1;
+const C: u8 = 2;
+const G: u8 = 4;
+const T: u8 = 8;
+const ANY: u8 = A | C | G | T;
+
+/// The bases an IUPAC nucleotide code stands for, as a set of the bits above,
+/// and none for any other symbol. `U` stands for `T`; case is ignored.
+pub fn bases(symbol: u8) -> u8 {
+    match symbol.to_ascii_uppercase() {
+        b'A' => A,
+        b'C' => C,
+        b'G' => G,
+        b'T' | b'U' => T,
+        b'R' => A | G,
+        b'Y' => C | T,
+        b'S' => C | G,
+        b'W' => A | T,
+        b'K' => G | T,
+        b'M' => A | C,
+        b'B' => C | G | T,
+        b'D' => A | G | T,
+        b'H' => A | C | T,
+        b'V' => A | C | G,
+        b'N' => ANY,
+        _ => 0
+    }
+}
+
+/// Whether two symbols are equal regardless of case, or IUPAC codes sharing a base,
+/// as `A` and `R` are.
+pub fn compatible(s: u8, r: u8) -> bool {
+    s.eq_ignore_ascii_case(&r) || bases(s) & bases(r) != 0
+}
+
+/// The chance that the bases two IUPAC codes stand for are the same, each of a code's
+/// bases being equally likely: 1 for `A` and `a`, 1/2 for `A` and `R`, 1/4 for `A` and `N`.
+pub fn match_probability(s: u8, r: u8) -> FScore {
+    let (s, r) = (bases(s), bases(r));
+    match (s & r).count_ones() {
+        0 => 0.0,
+        shared => FScore::from(shared) / FScore::from(s.count_ones() * r.count_ones())
+    }
+}
+
+/// The IUPAC code of the complementary bases, in the same case. `U` pairs with `A`;
+/// other symbols are their own complement.
+pub fn complement(symbol: u8) -> u8 {
+    let complement = match symbol.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' | b'U' => b'A',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        _ => return symbol
+    };
+    if symbol.is_ascii_lowercase() { complement.to_ascii_lowercase() } else { complement }
+}
+
+pub fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    sequence.iter().rev().map(|&symbol| complement(symbol)).collect()
+}
+
+/// How pairs with an `N` on either side score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NScoring {
+    /// Like the other ambiguity codes.
+    Ambiguous,
+    /// The same score whatever the other side, e.g. zero for unknown bases to count neither way.
+    Fixed(FScore),
+    Mismatch,
+}
+
+/// Match/mismatch scoring over IUPAC nucleotide codes, ignoring case. A pair of codes scores
+/// between a match and a mismatch by the chance that their bases are the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IupacAlignmentConfig {
+    pub match_score: FScore,
+    pub mismatch_penalty: FScore,
+    pub n_scoring: NScoring,
+    pub subject_gap_penalty: FScore,
+    pub subject_gap_extension_penalty: FScore,
+    pub reference_gap_penalty: FScore,
+    pub reference_gap_extension_penalty: FScore,
+}
+
+impl AlignmentConfig for IupacAlignmentConfig {
+    type Score = FScore;
+
+    fn get_substitution_score(&self, _pos: (usize, usize), s: u8, r: u8) -> FScore {
+        let is_n = |symbol: u8| bases(symbol) == ANY;
+        match self.n_scoring {
+            NScoring::Fixed(score) if is_n(s) || is_n(r) => score,
+            NScoring::Mismatch if is_n(s) || is_n(r) => self.mismatch_penalty,
+            _ => {
+                let p = match_probability(s, r);
+                p * self.match_score + (1.0 - p) * self.mismatch_penalty
+            }
+        }
+    }
+    fn get_subject_gap_opening_penalty(&self, _pos: usize) -> FScore {
+        self.subject_gap_penalty
+    }
+    fn get_reference_gap_opening_penalty(&self, _pos: usize) -> FScore {
+        self.reference_gap_penalty
+    }
+    fn get_subject_gap_extension_penalty(&self, _pos: usize) -> FScore {
+        self.subject_gap_extension_penalty
+    }
+    fn get_reference_gap_extension_penalty(&self, _pos: usize) -> FScore {
+        self.reference_gap_extension_penalty
+    }
+    /// Codes sharing a base match, save for `N` when it is not scored as ambiguous.
+    fn matches(&self, s: u8, r: u8) -> bool {
+        let is_n = |symbol: u8| bases(symbol) == ANY;
+        match self.n_scoring {
+            NScoring::Fixed(_) | NScoring::Mismatch if is_n(s) || is_n(r) => false,
+            _ => compatible(s, r)
+        }
+    }
+    fn accepts(&self, symbol: u8) -> bool {
+        bases(symbol) != 0
+    }
+    fn is_position_independent(&self) -> bool {
+        true
+    }
+    fn validate(&self) -> Result<()> {
+        check_score("match score", self.match_score)?;
+        check_penalty("mismatch penalty", self.mismatch_penalty)?;
+        if let NScoring::Fixed(score) = self.n_scoring {
+            check_score("N score", score)?;
+        }
+        check_penalty("subject gap penalty", self.subject_gap_penalty)?;
+        check_penalty("subject gap extension penalty", self.subject_gap_extension_penalty)?;
+        check_penalty("reference gap penalty", self.reference_gap_penalty)?;
+        check_penalty("reference gap extension penalty", self.reference_gap_extension_penalty)
+    }
+}
+
+impl NucleotideConfig for IupacAlignmentConfig {}
+
+pub type GlobalIupacAligner = GlobalAligner<IupacAlignmentConfig>;
+
+pub type LocalIupacAligner = LocalAligner<IupacAlignmentConfig>;
+
+pub type BandedIupacAligner = BandedAligner<IupacAlignmentConfig>;
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::Aligner;
+    use crate::alignment::Alignment;
+    use crate::config::AlignmentConfig;
+    use crate::error::{Error, Sequence};
+    use crate::iupac::{GlobalIupacAligner, IupacAlignmentConfig, NScoring, bases, compatible, complement, match_probability, reverse_complement};
+
+    const CONFIG: IupacAlignmentConfig = IupacAlignmentConfig {
+        match_score: 2.0,
+        mismatch_penalty: -2.0,
+        n_scoring: NScoring::Ambiguous,
+        subject_gap_penalty: -5.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -5.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    fn score(config: &IupacAlignmentConfig, s: u8, r: u8) -> f64 {
+        config.get_substitution_score((1, 1), s, r)
+    }
+
+    #[test]
+    fn test_match_probability() {
+        assert_eq!(match_probability(b'A', b'a'), 1.0);
+        assert_eq!(match_probability(b'U', b'T'), 1.0);
+        assert_eq!(match_probability(b'A', b'R'), 0.5);
+        assert_eq!(match_probability(b'R', b'R'), 0.5);
+        assert_eq!(match_probability(b'A', b'N'), 0.25);
+        assert_eq!(match_probability(b'R', b'Y'), 0.0);
+        assert_eq!(match_probability(b'A', b'_'), 0.0);
+    }
+
+    #[test]
+    fn test_compatible() {
+        assert!(compatible(b'a', b'A'));
+        assert!(compatible(b'R', b'g'));
+        assert!(compatible(b'N', b'C'));
+        assert!(!compatible(b'R', b'Y'));
+        assert!(!compatible(b'A', b'_'));
+        assert!(compatible(b'*', b'*'));
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement(b"ACGTTGCA"), b"TGCAACGT".to_vec());
+        assert_eq!(reverse_complement(b"acgRYkmBDHVNSWu-"), b"-aWSNBDHVkmRYcgt".to_vec());
+        assert_eq!(reverse_complement(b""), Vec::<u8>::new());
+        for &symbol in b"ACGTRYSWKMBDHVN".iter() {
+            assert_eq!(complement(complement(symbol)), symbol);
+            assert_eq!(bases(complement(symbol)).count_ones(), bases(symbol).count_ones());
+        }
+    }
+
+    #[test]
+    fn test_scores() {
+        assert_eq!(score(&CONFIG, b'a', b'A'), 2.0);
+        assert_eq!(score(&CONFIG, b'A', b'C'), -2.0);
+        assert_eq!(score(&CONFIG, b'A', b'R'), 0.0);
+        assert_eq!(score(&CONFIG, b'N', b'T'), -1.0);
+        assert!((score(&CONFIG, b'B', b'Y') + 2.0 / 3.0).abs() < 1e-12);
+        let fixed = IupacAlignmentConfig { n_scoring: NScoring::Fixed(0.0), ..CONFIG };
+        assert_eq!(score(&fixed, b'N', b'T'), 0.0);
+        assert_eq!(score(&fixed, b'n', b'N'), 0.0);
+        assert_eq!(score(&fixed, b'A', b'R'), 0.0);
+        let mismatch = IupacAlignmentConfig { n_scoring: NScoring::Mismatch, ..CONFIG };
+        assert_eq!(score(&mismatch, b'N', b'T'), -2.0);
+    }
+
+    #[test]
+    fn test_align() {
+        let aligner = GlobalIupacAligner::from(CONFIG);
+        assert_eq!(
+            aligner.align(b"acgtRCGT", b"ACGTACGYN"),
+            Ok(Alignment::from("acgtRCGT_", "ACGTACGYN", 7.0))
+        );
+        assert_eq!(
+            aligner.align(b"ACGX", b"ACGT"),
+            Err(Error::InvalidSymbol { sequence: Sequence::Subject, position: 3, symbol: b'X' })
+        );
+    }
+
+    #[test]
+    fn test_pairs() {
+        let alignment = Alignment::from("acgRNT_", "ACGAYTA", 0.0);
+        assert_eq!(alignment.aligned_sequences_with(&CONFIG).1, "|||||| ");
+        assert_eq!(alignment.aligned_sequences().1, "|||  | ");
+        let mismatch = IupacAlignmentConfig { n_scoring: NScoring::Mismatch, ..CONFIG };
+        assert_eq!(alignment.aligned_sequences_with(&mismatch).1, "|||| | ");
+        assert_eq!(Alignment::from("RA", "YC", 0.0).aligned_sequences_with(&CONFIG).1, "  ");
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = IupacAlignmentConfig { n_scoring: NScoring::Fixed(f64::NAN), ..CONFIG };
+        assert!(GlobalIupacAligner::from(config).align(b"ACGT", b"ACGN").is_err());
+    }
+}
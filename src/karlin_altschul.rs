@@ -0,0 +1,347 @@
+use crate::aligner::{Aligner, LocalAligner};
+use crate::config::AlignmentConfig;
+use crate::element::{FScore, Score};
+use crate::error::{Error, Result};
+use crate::xorshift::XorShift;
+use std::f64::consts::{LN_2, PI};
+
+/// Amino acid frequencies of Robinson and Robinson (1991), the background BLAST assumes.
+pub const ROBINSON_ROBINSON: &[(u8, FScore)] = &[
+    (b'A', 0.07805), (b'C', 0.01925), (b'D', 0.05364), (b'E', 0.06295), (b'F', 0.03856),
+    (b'G', 0.07377), (b'H', 0.02199), (b'I', 0.05142), (b'K', 0.05744), (b'L', 0.09019),
+    (b'M', 0.02243), (b'N', 0.04487), (b'P', 0.05203), (b'Q', 0.04264), (b'R', 0.05129),
+    (b'S', 0.07120), (b'T', 0.05841), (b'V', 0.06441), (b'W', 0.01330), (b'Y', 0.03216),
+];
+
+pub const UNIFORM_NUCLEOTIDES: &[(u8, FScore)] = &[(b'A', 0.25), (b'C', 0.25), (b'G', 0.25), (b'T', 0.25)];
+
+/// Gapped BLOSUM62 parameters precomputed by NCBI BLAST, as (gap existence, gap extension,
+/// lambda, K), BLAST charging `existence + length * extension` for a gap.
+const BLOSUM62: &[(u32, u32, FScore, FScore)] = &[
+    (11, 2, 0.297, 0.082),
+    (10, 2, 0.291, 0.075),
+    (9, 2, 0.279, 0.058),
+    (8, 2, 0.264, 0.045),
+    (7, 2, 0.239, 0.027),
+    (6, 2, 0.201, 0.012),
+    (13, 1, 0.292, 0.071),
+    (12, 1, 0.283, 0.059),
+    (11, 1, 0.267, 0.041),
+    (10, 1, 0.243, 0.024),
+    (9, 1, 0.206, 0.010),
+];
+
+/// Partial sums of the series for K stop below this term, or after `MAX_ITERATIONS` terms.
+const SUM_LIMIT: FScore = 0.0001;
+const MAX_ITERATIONS: usize = 100;
+
+const EULER_GAMMA: FScore = 0.577_215_664_901_532_9;
+
+/// The parameters of the extreme value distribution that local alignment scores of unrelated
+/// sequences follow (Karlin and Altschul, 1990), turning raw scores into bit scores and E-values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KarlinAltschul {
+    pub lambda: FScore,
+    pub k: FScore,
+}
+
+impl KarlinAltschul {
+    /// NCBI's ungapped BLOSUM62 parameters over `ROBINSON_ROBINSON`.
+    pub fn blosum62_ungapped() -> Self {
+        KarlinAltschul { lambda: 0.3176, k: 0.134 }
+    }
+
+    /// NCBI's gapped BLOSUM62 parameters for gaps whose first position costs `opening` and
+    /// every further one `extension`, as in `AaAlignmentConfig::with_gaps`. BLASTP's default
+    /// gap existence 11 and extension 1 is `opening` -12 and `extension` -1.
+    pub fn blosum62(opening: FScore, extension: FScore) -> Option<Self> {
+        BLOSUM62.iter()
+            .find(|&&(existence, ext, _, _)|
+                -extension == FScore::from(ext) && -opening == FScore::from(existence + ext))
+            .map(|&(_, _, lambda, k)| KarlinAltschul { lambda, k })
+    }
+
+    /// Ungapped parameters computed from the substitution scores of `config` at position
+    /// (1, 1), which must be integers, and the symbol frequencies of `background`.
+    pub fn ungapped<C: AlignmentConfig>(config: &C, background: &[(u8, FScore)]) -> Result<Self> {
+        let distribution = ScoreDistribution::of(config, background)?;
+        let lambda = distribution.lambda();
+        let k = distribution.k(lambda);
+        Ok(KarlinAltschul { lambda: lambda / distribution.span as FScore, k })
+    }
+
+    /// Parameters fitted to the scores of `samples` local alignments of random sequences of
+    /// `length` symbols drawn from `background`, by the method of moments (Altschul and Gish, 1996).
+    /// Works for any configuration, gapped ones included, at the cost of sampling noise.
+    pub fn estimate<C: AlignmentConfig>(
+        aligner: &LocalAligner<C>,
+        background: &[(u8, FScore)],
+        length: usize,
+        samples: usize,
+    ) -> Result<Self> {
+        if samples < 2 {
+            return Err(Error::InvalidConfig { parameter: "sample count", value: samples as FScore });
+        }
+        let mut random = Random::new(background);
+        let mut scores = Vec::with_capacity(samples);
+        for _ in 0..samples {
+            let subject = random.sequence(length);
+            let reference = random.sequence(length);
+            scores.push(aligner.score(&subject, &reference)?);
+        }
+        let mean = scores.iter().sum::<FScore>() / samples as FScore;
+        let variance = scores.iter().map(|score| (score - mean).powi(2)).sum::<FScore>() / (samples - 1) as FScore;
+        let lambda = PI / (6.0 * variance).sqrt();
+        let mode = mean - EULER_GAMMA / lambda;
+        let k = (lambda * mode).exp() / (length * length) as FScore;
+        Ok(KarlinAltschul { lambda, k })
+    }
+
+    /// The score in bits, comparable across scoring systems.
+    pub fn bit_score(&self, score: FScore) -> FScore {
+        (self.lambda * score - self.k.ln()) / LN_2
+    }
+
+    /// The number of alignments scoring at least `score` expected by chance when searching
+    /// a database of `database_length` symbols with a query of `query_length`.
+    pub fn e_value(&self, score: FScore, query_length: usize, database_length: usize) -> FScore {
+        self.k * query_length as FScore * database_length as FScore * (-self.lambda * score).exp()
+    }
+}
+
+/// The probabilities of the scores of aligning two random symbols, in units of the greatest
+/// common divisor `span` of the scores.
+struct ScoreDistribution {
+    lowest: i64,
+    span: i64,
+    /// Probabilities of the scores from `lowest` up, by steps of one unit.
+    probabilities: Vec<FScore>,
+}
+
+impl ScoreDistribution {
+    fn of<C: AlignmentConfig>(config: &C, background: &[(u8, FScore)]) -> Result<Self> {
+        let total: FScore = background.iter().map(|&(_, frequency)| frequency).sum();
+        let mut pairs = Vec::with_capacity(background.len() * background.len());
+        for &(s, p) in background {
+            for &(r, q) in background {
+                let score = config.get_substitution_score((1, 1), s, r).to_f64();
+                if score.fract() != 0.0 {
+                    return Err(Error::InvalidConfig { parameter: "substitution score", value: score });
+                }
+                pairs.push((score as i64, p * q / (total * total)));
+            }
+        }
+        let lowest = pairs.iter().map(|&(score, _)| score).min().unwrap_or(0);
+        let highest = pairs.iter().map(|&(score, _)| score).max().unwrap_or(0);
+        let span = pairs.iter()
+            .filter(|&&(_, probability)| probability > 0.0)
+            .fold(0, |span, &(score, _)| gcd(span, score))
+            .max(1);
+        let mut probabilities = vec![0.0; ((highest - lowest) / span + 1) as usize];
+        for (score, probability) in pairs {
+            probabilities[((score - lowest) / span) as usize] += probability;
+        }
+        let distribution = ScoreDistribution { lowest: lowest / span, span, probabilities };
+        let expected = distribution.expected(0.0);
+        if expected >= 0.0 || distribution.highest() <= 0 {
+            return Err(Error::InvalidConfig { parameter: "expected substitution score", value: expected * span as FScore });
+        }
+        Ok(distribution)
+    }
+
+    fn highest(&self) -> i64 {
+        self.lowest + self.probabilities.len() as i64 - 1
+    }
+
+    fn scores(&self) -> impl Iterator<Item=(FScore, FScore)> + '_ {
+        self.probabilities.iter()
+            .enumerate()
+            .map(move |(i, &probability)| ((self.lowest + i as i64) as FScore, probability))
+    }
+
+    /// The expected score under the distribution tilted by `lambda`.
+    fn expected(&self, lambda: FScore) -> FScore {
+        self.scores().map(|(score, probability)| probability * score * (lambda * score).exp()).sum()
+    }
+
+    /// The positive root of `sum(p(s) * exp(lambda * s)) = 1`, by bisection.
+    fn lambda(&self) -> FScore {
+        let moment = |lambda: FScore| -> FScore {
+            self.scores().map(|(score, probability)| probability * (lambda * score).exp()).sum()
+        };
+        let mut high = 1.0;
+        while moment(high) < 1.0 {
+            high *= 2.0;
+        }
+        let mut low = 0.0;
+        for _ in 0..100 {
+            let middle = (low + high) / 2.0;
+            if moment(middle) < 1.0 {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    /// K by the closed forms for scores reaching only one unit below or above zero, and by
+    /// the series of Karlin and Altschul (1990) otherwise, as NCBI BLAST computes it.
+    fn k(&self, lambda: FScore) -> FScore {
+        let (lowest, highest) = (self.lowest, self.highest());
+        let probability = |score: i64| self.probabilities[(score - lowest) as usize];
+        let target_mean = self.expected(lambda);
+        if lowest == -1 && highest == 1 {
+            return (probability(-1) - probability(1)).powi(2) / probability(-1);
+        }
+        if highest == 1 {
+            return target_mean * (1.0 - (-lambda).exp());
+        }
+        if lowest == -1 {
+            return self.expected(0.0).powi(2) / target_mean * (1.0 - (-lambda).exp());
+        }
+        let mut sum = 0.0;
+        let mut convolution = vec![1.0];
+        for iteration in 1..=MAX_ITERATIONS {
+            let mut next = vec![0.0; convolution.len() + self.probabilities.len() - 1];
+            for (i, &p) in convolution.iter().enumerate() {
+                for (j, &q) in self.probabilities.iter().enumerate() {
+                    next[i + j] += p * q;
+                }
+            }
+            convolution = next;
+            let first = lowest * iteration as i64;
+            let term: FScore = convolution.iter()
+                .enumerate()
+                .map(|(i, &p)| {
+                    let score = first + i as i64;
+                    if score < 0 { p * (lambda * score as FScore).exp() } else { p }
+                })
+                .sum::<FScore>() / iteration as FScore;
+            sum += term;
+            if term <= SUM_LIMIT {
+                break;
+            }
+        }
+        -(-2.0 * sum).exp() / (target_mean * (-lambda).exp_m1())
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+/// Sequences of symbols drawn from a background by a fixed xorshift generator, so that
+/// estimates are reproducible.
+struct Random<'a> {
+    background: &'a [(u8, FScore)],
+    total: FScore,
+    generator: XorShift,
+}
+
+impl<'a> Random<'a> {
+    fn new(background: &'a [(u8, FScore)]) -> Self {
+        let total = background.iter().map(|&(_, frequency)| frequency).sum();
+        Random { background, total, generator: XorShift::new(0x9e37_79b9_7f4a_7c15) }
+    }
+
+    fn sequence(&mut self, length: usize) -> Vec<u8> {
+        (0..length)
+            .map(|_| {
+                let mut threshold = self.generator.uniform() * self.total;
+                for &(symbol, frequency) in self.background {
+                    if threshold < frequency {
+                        return symbol;
+                    }
+                    threshold -= frequency;
+                }
+                self.background[self.background.len() - 1].0
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aa_aligner::AaAlignmentConfig;
+    use crate::aligner::LocalAligner;
+    use crate::error::Error;
+    use crate::karlin_altschul::{KarlinAltschul, ROBINSON_ROBINSON, UNIFORM_NUCLEOTIDES};
+    use crate::nt_aligner::NtAlignmentConfig;
+
+    fn nucleotides(match_score: f64, mismatch_penalty: f64, gap_penalty: f64) -> NtAlignmentConfig {
+        NtAlignmentConfig {
+            match_score,
+            mismatch_penalty,
+            subject_gap_penalty: gap_penalty,
+            subject_gap_extension_penalty: gap_penalty,
+            reference_gap_penalty: gap_penalty,
+            reference_gap_extension_penalty: gap_penalty,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() <= tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    #[test]
+    fn test_blosum62_ungapped() {
+        let parameters = KarlinAltschul::ungapped(&AaAlignmentConfig::blosum62(), ROBINSON_ROBINSON).unwrap();
+        let expected = KarlinAltschul::blosum62_ungapped();
+        assert_close(parameters.lambda, expected.lambda, 0.0005);
+        assert_close(parameters.k, expected.k, 0.001);
+    }
+
+    #[test]
+    fn test_closed_forms() {
+        let parameters = KarlinAltschul::ungapped(&nucleotides(1.0, -1.0, -1.0), UNIFORM_NUCLEOTIDES).unwrap();
+        assert_close(parameters.lambda, 3f64.ln(), 1e-9);
+        assert_close(parameters.k, 1.0 / 3.0, 1e-9);
+        let doubled = KarlinAltschul::ungapped(&nucleotides(2.0, -2.0, -1.0), UNIFORM_NUCLEOTIDES).unwrap();
+        assert_close(doubled.lambda, 3f64.ln() / 2.0, 1e-9);
+        assert_close(doubled.k, 1.0 / 3.0, 1e-9);
+        let blastn = KarlinAltschul::ungapped(&nucleotides(1.0, -3.0, -1.0), UNIFORM_NUCLEOTIDES).unwrap();
+        assert_close(blastn.lambda, 1.374, 0.0005);
+        assert_close(blastn.k, 0.711, 0.0005);
+    }
+
+    #[test]
+    fn test_invalid_distributions() {
+        assert_eq!(
+            KarlinAltschul::ungapped(&nucleotides(3.0, -1.0, -1.0), UNIFORM_NUCLEOTIDES),
+            Err(Error::InvalidConfig { parameter: "expected substitution score", value: 0.0 })
+        );
+        assert_eq!(
+            KarlinAltschul::ungapped(&nucleotides(1.0, -1.5, -1.0), UNIFORM_NUCLEOTIDES),
+            Err(Error::InvalidConfig { parameter: "substitution score", value: -1.5 })
+        );
+    }
+
+    #[test]
+    fn test_blosum62_table() {
+        assert_eq!(KarlinAltschul::blosum62(-12.0, -1.0), Some(KarlinAltschul { lambda: 0.267, k: 0.041 }));
+        assert_eq!(KarlinAltschul::blosum62(-13.0, -2.0), Some(KarlinAltschul { lambda: 0.297, k: 0.082 }));
+        assert_eq!(KarlinAltschul::blosum62(-5.0, -1.0), None);
+    }
+
+    #[test]
+    fn test_bit_score_and_e_value() {
+        let parameters = KarlinAltschul::blosum62(-12.0, -1.0).unwrap();
+        assert_close(parameters.bit_score(100.0), 43.1, 0.05);
+        let e_value = parameters.e_value(100.0, 250, 1_000_000);
+        assert_close(e_value, 250.0 * 1e6 * 2f64.powf(-parameters.bit_score(100.0)), 1e-12);
+        assert!(parameters.e_value(120.0, 250, 1_000_000) < e_value);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let aligner = LocalAligner::from(nucleotides(1.0, -1.0, -100.0));
+        let estimate = KarlinAltschul::estimate(&aligner, UNIFORM_NUCLEOTIDES, 300, 300).unwrap();
+        assert_close(estimate.lambda, 3f64.ln(), 0.15);
+        assert!(estimate.k > 0.1 && estimate.k < 1.0, "{:?}", estimate);
+        assert_eq!(
+            KarlinAltschul::estimate(&aligner, UNIFORM_NUCLEOTIDES, 300, 1),
+            Err(Error::InvalidConfig { parameter: "sample count", value: 1.0 })
+        );
+    }
+}
@@ -1,7 +1,31 @@
 pub mod aligner;
 pub mod nt_aligner;
+pub mod aa_aligner;
+pub mod iupac;
+pub mod quality;
+pub mod strand;
+pub mod msa;
+pub mod poa;
+pub mod banded;
+pub mod edit_distance;
+#[cfg(feature = "parallel")]
+pub mod batch;
+pub mod substitution_matrix;
+pub mod profile;
 pub mod alignment;
+pub mod cigar;
+pub mod sam;
+pub mod stats;
+pub mod karlin_altschul;
+pub mod fastx;
 pub mod config;
+pub mod error;
 mod matrix;
 mod element;
-mod iterators;
\ No newline at end of file
+mod iterators;
+mod hirschberg;
+mod striped;
+mod wavefront;
+mod xorshift;
+
+pub use error::{Error, Result};
\ No newline at end of file
@@ -1,27 +1,44 @@
 #![allow(dead_code)]
 
 use ndarray::{Array2, FixedInitializer, arr2};
-use crate::element::{Op, Element};
+use crate::element::{Op, Element, FScore, Score};
 
 pub type Idx = (usize, usize);
 
-pub type Matrix = Array2<Element>;
+pub type Matrix<S = FScore> = Array2<Element<S>>;
 
-pub fn of(num_rows: usize, num_columns: usize) -> Matrix {
+pub fn of<S: Score>(num_rows: usize, num_columns: usize) -> Matrix<S> {
     Matrix::from_elem((num_rows, num_columns), Element::default())
 }
 
-pub fn from_elements<V>(elements: &[V]) -> Matrix
-    where V: Clone + FixedInitializer<Elem=Element> {
-    arr2(&elements)
+/// A matrix of default elements over the storage of `elements`.
+pub fn reusing<S: Score>(mut elements: Vec<Element<S>>, num_rows: usize, num_columns: usize) -> Matrix<S> {
+    elements.clear();
+    elements.resize(num_rows * num_columns, Element::default());
+    Matrix::from_shape_vec((num_rows, num_columns), elements).unwrap()
 }
 
-pub fn move_back(element: &Element, position: Idx) -> Idx {
+pub fn from_elements<S: Score, V>(elements: &[V]) -> Matrix<S>
+    where V: Clone + FixedInitializer<Elem=Element<S>> {
+    arr2(elements)
+}
+
+pub fn move_back(op: Op, position: Idx) -> Idx {
     let (row, column) = position;
-    match element.op {
+    match op {
         Op::MATCH => (row - 1, column - 1),
         Op::INSERT => (row - 1, column),
         Op::DELETE => (row, column - 1),
         _ => unreachable!()
     }
+}
+
+pub fn move_forward(op: Op, position: Idx) -> Idx {
+    let (row, column) = position;
+    match op {
+        Op::MATCH => (row + 1, column + 1),
+        Op::INSERT => (row + 1, column),
+        Op::DELETE => (row, column + 1),
+        _ => unreachable!()
+    }
 }
\ No newline at end of file
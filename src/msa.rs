@@ -0,0 +1,401 @@
+use crate::aligner::{validate, Aligner, GlobalAligner};
+use crate::config::AlignmentConfig;
+use crate::element::{FScore, Op, Score};
+use crate::error::{Error, Result};
+use crate::fastx::Record;
+use std::io::{self, Write};
+use std::iter;
+
+/// The gap symbol of multiple alignment rows, as FASTA and Clustal write it.
+pub const GAP: u8 = b'-';
+
+/// Residues per line of the written alignments.
+const LINE_WIDTH: usize = 60;
+
+/// How the guide tree joins the sequences.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeMethod {
+    /// Joins the closest clusters, averaging the distances of their members.
+    Upgma,
+    /// Joins the clusters that minimize the total branch length (Saitou & Nei, 1987).
+    NeighborJoining,
+}
+
+/// The order in which a progressive alignment merges the sequences, by their input indices.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuideTree {
+    Leaf(usize),
+    Node(Box<GuideTree>, Box<GuideTree>),
+}
+
+impl GuideTree {
+    /// Joins `n` sequences by a symmetric matrix of their pairwise distances.
+    pub fn build(distances: &[Vec<FScore>], method: TreeMethod) -> Option<Self> {
+        let mut clusters: Vec<(GuideTree, usize)> = (0..distances.len())
+            .map(|i| (GuideTree::Leaf(i), 1))
+            .collect();
+        let mut distances = distances.to_vec();
+        while clusters.len() > 1 {
+            let n = clusters.len();
+            let sums: Vec<FScore> = distances.iter().map(|row| row.iter().sum()).collect();
+            let criterion = |i: usize, j: usize| match method {
+                TreeMethod::Upgma => distances[i][j],
+                TreeMethod::NeighborJoining => (n - 2) as FScore * distances[i][j] - sums[i] - sums[j],
+            };
+            let (i, j) = (0..n)
+                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+                .fold((0, 1), |best, pair| if criterion(pair.0, pair.1) < criterion(best.0, best.1) { pair } else { best });
+            let (size_i, size_j) = (clusters[i].1, clusters[j].1);
+            let joined: Vec<FScore> = (0..n)
+                .filter(|&k| k != i && k != j)
+                .map(|k| match method {
+                    TreeMethod::Upgma =>
+                        (size_i as FScore * distances[i][k] + size_j as FScore * distances[j][k]) / (size_i + size_j) as FScore,
+                    TreeMethod::NeighborJoining => (distances[i][k] + distances[j][k] - distances[i][j]) / 2.0,
+                })
+                .collect();
+            let (right, _) = clusters.remove(j);
+            let (left, _) = clusters.remove(i);
+            for index in [j, i].iter() {
+                distances.remove(*index);
+                distances.iter_mut().for_each(|row| { row.remove(*index); });
+            }
+            distances.iter_mut().zip(joined.iter()).for_each(|(row, &d)| row.push(d));
+            distances.push(joined.into_iter().chain(iter::once(0.0)).collect());
+            clusters.push((GuideTree::Node(Box::new(left), Box::new(right)), size_i + size_j));
+        }
+        clusters.pop().map(|(tree, _)| tree)
+    }
+}
+
+/// Aligned sequences, gaps written as `GAP`, in the order they were given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultipleAlignment {
+    pub names: Vec<String>,
+    pub rows: Vec<Vec<u8>>,
+}
+
+impl MultipleAlignment {
+    pub fn columns(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.len())
+    }
+
+    pub fn write_fasta<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for (name, row) in self.names.iter().zip(self.rows.iter()) {
+            writeln!(out, ">{}", name)?;
+            for line in row.chunks(LINE_WIDTH) {
+                out.write_all(line)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the Clustal format, marking the columns of a single residue with `*`.
+    pub fn write_clustal<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "CLUSTAL W multiple sequence alignment")?;
+        let width = self.names.iter().map(|name| name.len()).max().unwrap_or(0) + 4;
+        for start in (0..self.columns()).step_by(LINE_WIDTH) {
+            let end = (start + LINE_WIDTH).min(self.columns());
+            writeln!(out)?;
+            for (name, row) in self.names.iter().zip(self.rows.iter()) {
+                writeln!(out, "{:<width$}{}", name, String::from_utf8_lossy(&row[start..end]), width = width)?;
+            }
+            let conservation: String = (start..end)
+                .map(|column| if self.is_conserved(column) { '*' } else { ' ' })
+                .collect();
+            writeln!(out, "{:<width$}{}", "", conservation, width = width)?;
+        }
+        Ok(())
+    }
+
+    fn is_conserved(&self, column: usize) -> bool {
+        let first = self.rows[0][column];
+        first != GAP && self.rows.iter().all(|row| row[column].eq_ignore_ascii_case(&first))
+    }
+}
+
+/// Progressive multiple alignment: pairwise distances from the global alignments of `aligner`,
+/// a guide tree over them, and profile-profile alignments up the tree. Profiles score by the
+/// substitution scores of `aligner` averaged over their residue pairs, and gaps against
+/// a column by its share of residues. Profile columns have no sequence positions, so the
+/// scoring must be position-independent.
+pub struct ProgressiveAligner<C> {
+    pub aligner: GlobalAligner<C>,
+    pub method: TreeMethod,
+}
+
+impl<C: AlignmentConfig> From<C> for ProgressiveAligner<C> {
+    fn from(config: C) -> Self {
+        ProgressiveAligner { aligner: GlobalAligner::from(config), method: TreeMethod::Upgma }
+    }
+}
+
+impl<C: AlignmentConfig> ProgressiveAligner<C> {
+    /// One minus the identity of every pair's global alignment.
+    pub fn distances(&self, sequences: &[Record]) -> Result<Vec<Vec<FScore>>> {
+        let mut distances = vec![vec![0.0; sequences.len()]; sequences.len()];
+        for i in 0..sequences.len() {
+            for j in i + 1..sequences.len() {
+                let (a, b) = (&sequences[i].sequence, &sequences[j].sequence);
+                let identity = self.aligner.align(a, b)?.stats(a.len(), b.len()).identity();
+                distances[i][j] = 1.0 - identity;
+                distances[j][i] = 1.0 - identity;
+            }
+        }
+        Ok(distances)
+    }
+
+    pub fn guide_tree(&self, sequences: &[Record]) -> Result<Option<GuideTree>> {
+        Ok(GuideTree::build(&self.distances(sequences)?, self.method))
+    }
+
+    pub fn align(&self, sequences: &[Record]) -> Result<MultipleAlignment> {
+        if !self.aligner.config.is_position_independent() {
+            return Err(Error::InvalidConfig { parameter: "position-dependent scoring", value: FScore::NAN });
+        }
+        let mut rows = vec![Vec::new(); sequences.len()];
+        if let Some(tree) = self.guide_tree(sequences)? {
+            let profile = self.align_tree(&tree, sequences)?;
+            for (member, row) in profile.members.into_iter().zip(profile.rows) {
+                rows[member] = row;
+            }
+        }
+        Ok(MultipleAlignment { names: sequences.iter().map(|record| record.name.clone()).collect(), rows })
+    }
+
+    fn align_tree(&self, tree: &GuideTree, sequences: &[Record]) -> Result<Profile> {
+        match tree {
+            GuideTree::Leaf(i) => {
+                let sequence = &sequences[*i].sequence;
+                validate(&self.aligner.config, sequence, sequence)?;
+                Ok(Profile::of(vec![*i], vec![sequence.clone()]))
+            }
+            GuideTree::Node(left, right) => {
+                let (left, right) = (self.align_tree(left, sequences)?, self.align_tree(right, sequences)?);
+                self.align_profiles(left, right)
+            }
+        }
+    }
+
+    /// Aligns the columns of two profiles by a global aligner over placeholder sequences
+    /// of their lengths, the scores looked up by position.
+    fn align_profiles(&self, subject: Profile, reference: Profile) -> Result<Profile> {
+        let pair = ProfilePair { config: &self.aligner.config, subject: &subject, reference: &reference };
+        let aligner = GlobalAligner { config: pair, free_end_gaps: self.aligner.free_end_gaps };
+        let alignment = aligner.align(&vec![0; subject.len()], &vec![0; reference.len()])?;
+        let mut rows: Vec<Vec<u8>> = vec![Vec::with_capacity(alignment.anchors.len()); subject.rows.len() + reference.rows.len()];
+        for anchor in alignment.anchors.iter().rev().skip(1) {
+            let (row, col) = anchor.idx;
+            let (s, r) = match anchor.op {
+                Op::MATCH => (Some(row - 1), Some(col - 1)),
+                Op::INSERT => (Some(row - 1), None),
+                Op::DELETE => (None, Some(col - 1)),
+                Op::START => (None, None),
+            };
+            let aligned = subject.rows.iter().map(|row| (row, s))
+                .chain(reference.rows.iter().map(|row| (row, r)));
+            for (merged, (aligned, column)) in rows.iter_mut().zip(aligned) {
+                merged.push(column.map_or(GAP, |c| aligned[c]));
+            }
+        }
+        let members = subject.members.iter().chain(reference.members.iter()).copied().collect();
+        Ok(Profile::of(members, rows))
+    }
+}
+
+/// Aligned rows of some of the sequences, with the residue frequencies of every column.
+struct Profile {
+    members: Vec<usize>,
+    rows: Vec<Vec<u8>>,
+    /// By column, the residues in it with their shares of the rows.
+    frequencies: Vec<Vec<(u8, FScore)>>,
+    /// By column, the share of the rows holding a residue.
+    occupancy: Vec<FScore>,
+}
+
+impl Profile {
+    fn of(members: Vec<usize>, rows: Vec<Vec<u8>>) -> Self {
+        let share = 1.0 / rows.len() as FScore;
+        let frequencies: Vec<Vec<(u8, FScore)>> = (0..rows[0].len())
+            .map(|column| {
+                let mut residues: Vec<(u8, FScore)> = Vec::new();
+                for residue in rows.iter().map(|row| row[column]).filter(|&r| r != GAP) {
+                    match residues.iter_mut().find(|(r, _)| *r == residue) {
+                        Some((_, frequency)) => *frequency += share,
+                        None => residues.push((residue, share))
+                    }
+                }
+                residues
+            })
+            .collect();
+        let occupancy = frequencies.iter()
+            .map(|residues| residues.iter().map(|(_, frequency)| frequency).sum())
+            .collect();
+        Profile { members, rows, frequencies, occupancy }
+    }
+
+    fn len(&self) -> usize {
+        self.frequencies.len()
+    }
+}
+
+/// Scores the columns of two profiles against each other, taking the scores and penalties
+/// of `config` at its first positions.
+struct ProfilePair<'a, C> {
+    config: &'a C,
+    subject: &'a Profile,
+    reference: &'a Profile,
+}
+
+impl<'a, C: AlignmentConfig> AlignmentConfig for ProfilePair<'a, C> {
+    type Score = FScore;
+
+    fn get_substitution_score(&self, (row, col): (usize, usize), _s: u8, _r: u8) -> FScore {
+        let mut score = 0.0;
+        for &(s, s_frequency) in self.subject.frequencies[row - 1].iter() {
+            for &(r, r_frequency) in self.reference.frequencies[col - 1].iter() {
+                score += s_frequency * r_frequency * self.config.get_substitution_score((1, 1), s, r).to_f64();
+            }
+        }
+        score
+    }
+    fn get_subject_gap_opening_penalty(&self, pos: usize) -> FScore {
+        self.reference.occupancy[pos - 1] * self.config.get_subject_gap_opening_penalty(1).to_f64()
+    }
+    fn get_reference_gap_opening_penalty(&self, pos: usize) -> FScore {
+        self.subject.occupancy[pos - 1] * self.config.get_reference_gap_opening_penalty(1).to_f64()
+    }
+    fn get_subject_gap_extension_penalty(&self, pos: usize) -> FScore {
+        self.reference.occupancy[pos - 1] * self.config.get_subject_gap_extension_penalty(1).to_f64()
+    }
+    fn get_reference_gap_extension_penalty(&self, pos: usize) -> FScore {
+        self.subject.occupancy[pos - 1] * self.config.get_reference_gap_extension_penalty(1).to_f64()
+    }
+    fn subject_length(&self) -> Option<usize> {
+        Some(self.subject.len())
+    }
+    fn reference_length(&self) -> Option<usize> {
+        Some(self.reference.len())
+    }
+    fn validate(&self) -> Result<()> {
+        self.config.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{Error, Sequence};
+    use crate::fastx::Record;
+    use crate::msa::{GuideTree, MultipleAlignment, ProgressiveAligner, TreeMethod, GAP};
+    use crate::nt_aligner::NtAlignmentConfig;
+    use crate::quality::QualityConfig;
+
+    const CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 2.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -4.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -4.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    fn records(sequences: &[&str]) -> Vec<Record> {
+        sequences.iter()
+            .enumerate()
+            .map(|(i, sequence)| Record { name: format!("seq{}", i + 1), sequence: sequence.as_bytes().to_vec(), quality: None })
+            .collect()
+    }
+
+    fn node(left: GuideTree, right: GuideTree) -> GuideTree {
+        GuideTree::Node(Box::new(left), Box::new(right))
+    }
+
+    #[test]
+    fn test_upgma() {
+        let distances = vec![
+            vec![0.0, 0.1, 0.6, 0.7],
+            vec![0.1, 0.0, 0.65, 0.6],
+            vec![0.6, 0.65, 0.0, 0.2],
+            vec![0.7, 0.6, 0.2, 0.0],
+        ];
+        assert_eq!(
+            GuideTree::build(&distances, TreeMethod::Upgma),
+            Some(node(node(GuideTree::Leaf(0), GuideTree::Leaf(1)), node(GuideTree::Leaf(2), GuideTree::Leaf(3))))
+        );
+        assert_eq!(GuideTree::build(&[vec![0.0]], TreeMethod::Upgma), Some(GuideTree::Leaf(0)));
+        assert_eq!(GuideTree::build(&[], TreeMethod::Upgma), None);
+    }
+
+    #[test]
+    fn test_neighbor_joining() {
+        let distances = vec![
+            vec![0.0, 5.0, 9.0, 9.0, 8.0],
+            vec![5.0, 0.0, 10.0, 10.0, 9.0],
+            vec![9.0, 10.0, 0.0, 8.0, 7.0],
+            vec![9.0, 10.0, 8.0, 0.0, 3.0],
+            vec![8.0, 9.0, 7.0, 3.0, 0.0],
+        ];
+        let ab = node(GuideTree::Leaf(0), GuideTree::Leaf(1));
+        assert_eq!(
+            GuideTree::build(&distances, TreeMethod::NeighborJoining),
+            Some(node(node(GuideTree::Leaf(2), ab), node(GuideTree::Leaf(3), GuideTree::Leaf(4))))
+        );
+    }
+
+    #[test]
+    fn test_align() {
+        let sequences = records(&["ACGTTGCAAGTCCGATG", "ACGTTGCAGTCCGATG", "ACGTTGCAAGTCCGATG", "ACGTAGCAAGTCCGTG"]);
+        for &method in [TreeMethod::Upgma, TreeMethod::NeighborJoining].iter() {
+            let aligner = ProgressiveAligner { method, ..ProgressiveAligner::from(CONFIG) };
+            let msa = aligner.align(&sequences).unwrap();
+            assert_eq!(msa.names, vec!["seq1", "seq2", "seq3", "seq4"]);
+            assert_eq!(msa.columns(), 17);
+            assert_eq!(msa.rows[0], b"ACGTTGCAAGTCCGATG".to_vec());
+            assert_eq!(msa.rows[2], b"ACGTTGCAAGTCCGATG".to_vec());
+            for (row, record) in msa.rows.iter().zip(sequences.iter()) {
+                let ungapped: Vec<u8> = row.iter().copied().filter(|&symbol| symbol != GAP).collect();
+                assert_eq!(ungapped, record.sequence);
+            }
+            assert_eq!(msa.rows[1].iter().filter(|&&symbol| symbol == GAP).count(), 1);
+            assert_eq!(&msa.rows[3][..13], b"ACGTAGCAAGTCC");
+        }
+    }
+
+    #[test]
+    fn test_few_sequences() {
+        let aligner = ProgressiveAligner::from(CONFIG);
+        assert_eq!(aligner.align(&[]).unwrap().columns(), 0);
+        assert_eq!(aligner.align(&records(&["ACGT"])).unwrap().rows, vec![b"ACGT".to_vec()]);
+        assert_eq!(
+            aligner.align(&records(&["ACGT", "ACXT"])),
+            Err(Error::InvalidSymbol { sequence: Sequence::Reference, position: 2, symbol: b'X' })
+        );
+    }
+
+    #[test]
+    fn test_position_dependent_scoring() {
+        let qualities = QualityConfig::new(&CONFIG, b"IIII");
+        assert!(matches!(
+            ProgressiveAligner::from(qualities).align(&records(&["ACGT", "ACGT"])),
+            Err(Error::InvalidConfig { parameter: "position-dependent scoring", .. })
+        ));
+    }
+
+    #[test]
+    fn test_write() {
+        let msa = MultipleAlignment {
+            names: vec!["a".to_string(), "seq2".to_string()],
+            rows: vec![b"ACGT-A".to_vec(), b"ACCTTA".to_vec()],
+        };
+        let mut fasta = Vec::new();
+        msa.write_fasta(&mut fasta).unwrap();
+        assert_eq!(String::from_utf8(fasta).unwrap(), ">a\nACGT-A\n>seq2\nACCTTA\n");
+        let mut clustal = Vec::new();
+        msa.write_clustal(&mut clustal).unwrap();
+        assert_eq!(
+            String::from_utf8(clustal).unwrap(),
+            "CLUSTAL W multiple sequence alignment\n\na       ACGT-A\nseq2    ACCTTA\n        ** * *\n"
+        );
+    }
+}
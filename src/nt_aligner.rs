@@ -1,136 +1,103 @@
+use crate::alignment::same_symbol;
 use crate::config::AlignmentConfig;
-use crate::aligner::{Aligner};
-use crate::alignment::{Alignment, AlignmentBuilder};
-use crate::matrix::{Matrix, Idx};
-use crate::{matrix};
-use crate::iterators::{accumulate, set_accumulated};
-use crate::element::{FScore, Element, Op};
-
-pub struct NtAlignmentConfig {
-    pub match_score: FScore,
-    pub mismatch_penalty: FScore,
-    pub subject_gap_penalty: FScore,
-    pub reference_gap_penalty: FScore,
+use crate::aligner::{GlobalAligner, LocalAligner};
+use crate::banded::BandedAligner;
+use crate::element::{FScore, Score};
+use crate::error::{Result, check_penalty, check_score};
+use crate::strand::NucleotideConfig;
+
+/// Nucleotides and IUPAC ambiguity codes.
+const NUCLEOTIDES: &[u8] = b"ACGTURYSWKMBDHVN";
+
+/// Match/mismatch scoring with affine gaps, in any score type; `NtAlignmentConfig<i32>`
+/// aligns in exact integer arithmetic.
+pub struct NtAlignmentConfig<S = FScore> {
+    pub match_score: S,
+    pub mismatch_penalty: S,
+    pub subject_gap_penalty: S,
+    pub subject_gap_extension_penalty: S,
+    pub reference_gap_penalty: S,
+    pub reference_gap_extension_penalty: S,
 }
 
-impl AlignmentConfig for NtAlignmentConfig {
-    fn get_substitution_score(&self, _pos: (usize, usize), s: u8, r: u8) -> FScore {
-        if s == r { self.match_score } else { self.mismatch_penalty }
+impl<S: Score> AlignmentConfig for NtAlignmentConfig<S> {
+    type Score = S;
+
+    fn get_substitution_score(&self, _pos: (usize, usize), s: u8, r: u8) -> S {
+        if same_symbol(s, r) { self.match_score } else { self.mismatch_penalty }
     }
-    fn get_subject_gap_opening_penalty(&self, _pos: usize) -> FScore {
+    fn get_subject_gap_opening_penalty(&self, _pos: usize) -> S {
         self.subject_gap_penalty
     }
-    fn get_reference_gap_opening_penalty(&self, _pos: usize) -> FScore {
+    fn get_reference_gap_opening_penalty(&self, _pos: usize) -> S {
         self.reference_gap_penalty
     }
-}
-
-pub struct GlobalNtAligner {
-    pub config: NtAlignmentConfig
-}
-
-impl From<NtAlignmentConfig> for GlobalNtAligner {
-    fn from(config: NtAlignmentConfig) -> Self {
-        GlobalNtAligner { config }
-    }
-}
-
-impl Aligner<NtAlignmentConfig> for GlobalNtAligner {
-    fn fill_top_row(&self, mtx: &mut Matrix) {
-        set_accumulated(
-            accumulate(
-                mtx.cols(),
-                |n| self.config.get_subject_gap_opening_penalty(n),
-            ),
-            mtx.row_mut(0).iter_mut(),
-            |s| deletion(s),
-        )
+    fn get_subject_gap_extension_penalty(&self, _pos: usize) -> S {
+        self.subject_gap_extension_penalty
     }
-
-    fn fill_left_column(&self, mtx: &mut Matrix) {
-        set_accumulated(
-            accumulate(
-                mtx.rows(),
-                |n| self.config.get_reference_gap_opening_penalty(n),
-            ),
-            mtx.column_mut(0).iter_mut(),
-            |s| insertion(s),
-        );
+    fn get_reference_gap_extension_penalty(&self, _pos: usize) -> S {
+        self.reference_gap_extension_penalty
     }
-
-    fn fill(&self, mtx: &mut Matrix, subject: &[u8], reference: &[u8]) {
-        for row in 1..mtx.rows() {
-            let s = subject[row - 1];
-            for col in 1..mtx.cols() {
-                let r = reference[col - 1];
-                mtx[(row, col)] = select(
-                    mtx[(row - 1, col - 1)] +
-                        self.config.get_substitution_score((row, col), s, r),
-                    mtx[(row - 1, col)] +
-                        self.config.get_reference_gap_opening_penalty(row),
-                    mtx[(row, col - 1)] +
-                        self.config.get_subject_gap_opening_penalty(col),
-                )
-            }
-        }
+    fn accepts(&self, symbol: u8) -> bool {
+        NUCLEOTIDES.contains(&symbol.to_ascii_uppercase())
     }
-
-    fn end_idx(&self, mtx: &Matrix) -> Idx {
-        (mtx.rows() - 1, mtx.cols() - 1)
+    fn is_position_independent(&self) -> bool {
+        true
     }
-
-    fn trace_back(&self, mtx: &Matrix, end_index: Idx, subject: &[u8], reference: &[u8]) -> Alignment {
-        let mut builder = AlignmentBuilder::new(subject, reference);
-        let mut cursor = end_index;
-        while cursor != (0, 0) {
-            let element = mtx[cursor];
-            builder.take(element.op, cursor);
-            cursor = matrix::move_back(&element, cursor);
-        }
-        builder.take(Op::START, cursor);
-        builder.build(mtx[end_index].score)
+    fn validate(&self) -> Result<()> {
+        check_score("match score", self.match_score)?;
+        check_penalty("mismatch penalty", self.mismatch_penalty)?;
+        check_penalty("subject gap penalty", self.subject_gap_penalty)?;
+        check_penalty("subject gap extension penalty", self.subject_gap_extension_penalty)?;
+        check_penalty("reference gap penalty", self.reference_gap_penalty)?;
+        check_penalty("reference gap extension penalty", self.reference_gap_extension_penalty)
     }
 }
 
-fn select(substitution_score: FScore, insertion_score: FScore, deletion_score: FScore) -> Element {
-    if substitution_score >= insertion_score && substitution_score >= deletion_score {
-        substitution(substitution_score)
-    } else if insertion_score >= deletion_score {
-        insertion(insertion_score)
-    } else {
-        deletion(deletion_score)
-    }
-}
+impl<S: Score> NucleotideConfig for NtAlignmentConfig<S> {}
 
-pub fn insertion(score: FScore) -> Element {
-    Element { op: Op::INSERT, score }
-}
+pub type GlobalNtAligner<S = FScore> = GlobalAligner<NtAlignmentConfig<S>>;
 
-pub fn deletion(score: FScore) -> Element {
-    Element { op: Op::DELETE, score }
-}
+pub type LocalNtAligner<S = FScore> = LocalAligner<NtAlignmentConfig<S>>;
 
-pub fn substitution(score: FScore) -> Element {
-    Element { op: Op::MATCH, score }
-}
+pub type BandedNtAligner<S = FScore> = BandedAligner<NtAlignmentConfig<S>>;
 
 #[cfg(test)]
 mod tests {
-    use crate::nt_aligner::{GlobalNtAligner, NtAlignmentConfig, deletion, insertion, substitution};
-    use crate::aligner::Aligner;
+    use crate::nt_aligner::{BandedNtAligner, GlobalNtAligner, LocalNtAligner, NtAlignmentConfig};
+    use crate::banded::Band;
+    use crate::aligner::{Aligner, AlignerWorkspace, FreeEndGaps, deletion, insertion, substitution};
     use crate::matrix;
     use crate::alignment::Alignment;
-    use crate::element::{FScore, Element};
-
-    const ALIGNER: GlobalNtAligner = GlobalNtAligner {
-        config: NtAlignmentConfig {
-            match_score: 1.0,
-            mismatch_penalty: -1.0,
-            subject_gap_penalty: -1.0,
-            reference_gap_penalty: -1.0,
-        }
+    use crate::element::{FScore, Element, Score};
+    use crate::error::{Error, Sequence};
+
+    const CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -1.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -1.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    const AFFINE_CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -2.0,
+        subject_gap_extension_penalty: -0.5,
+        reference_gap_penalty: -2.0,
+        reference_gap_extension_penalty: -0.5,
     };
 
+    const ALIGNER: GlobalNtAligner = GlobalNtAligner { config: CONFIG, free_end_gaps: FreeEndGaps::NONE };
+
+    const LOCAL_ALIGNER: LocalNtAligner = LocalNtAligner { config: CONFIG };
+
+    const AFFINE_ALIGNER: GlobalNtAligner = GlobalNtAligner { config: AFFINE_CONFIG, free_end_gaps: FreeEndGaps::NONE };
+
+    const AFFINE_LOCAL_ALIGNER: LocalNtAligner = LocalNtAligner { config: AFFINE_CONFIG };
+
     #[test]
     fn test_fill_top_row() {
         let mut mtx = matrix::of(2, 3);
@@ -187,7 +154,7 @@ mod tests {
             ]
         );
         assert_eq!(
-            ALIGNER.trace_back(&mtx, (1, 1), "A".as_bytes(), "A".as_bytes()),
+            ALIGNER.trace_back(&mtx, (1, 1), "A".as_bytes(), "A".as_bytes()).unwrap(),
             Alignment::from("A", "A", 1.0)
         );
     }
@@ -201,7 +168,7 @@ mod tests {
             ]
         );
         assert_eq!(
-            ALIGNER.trace_back(&mtx, (1, 0), &['A' as u8], &[]),
+            ALIGNER.trace_back(&mtx, (1, 0), b"A", &[]).unwrap(),
             Alignment::from("A", "_", -1.0)
         );
     }
@@ -214,7 +181,7 @@ mod tests {
             ]
         );
         assert_eq!(
-            ALIGNER.trace_back(&mtx, (0, 1), &[], &['A' as u8]),
+            ALIGNER.trace_back(&mtx, (0, 1), &[], b"A").unwrap(),
             Alignment::from("_", "A", -1.0)
         );
     }
@@ -222,15 +189,22 @@ mod tests {
     #[test]
     fn test_match() {
         assert_eq!(
-            ALIGNER.align(b"AGCT", b"AGCT"),
+            ALIGNER.align(b"AGCT", b"AGCT").unwrap(),
             Alignment::from("AGCT", "AGCT", 4.0)
         )
     }
 
+    #[test]
+    fn test_match_ignores_case() {
+        let alignment = ALIGNER.align(b"acgt", b"ACGT").unwrap();
+        assert_eq!(alignment, Alignment::from("acgt", "ACGT", 4.0));
+        assert_eq!(alignment.stats(4, 4).matches, 4);
+    }
+
     #[test]
     fn test_mismatch() {
         assert_eq!(
-            ALIGNER.align(b"AGAT", b"AGCT"),
+            ALIGNER.align(b"AGAT", b"AGCT").unwrap(),
             Alignment::from("AGAT", "AGCT", 2.0)
         )
     }
@@ -238,7 +212,7 @@ mod tests {
     #[test]
     fn test_insertion() {
         assert_eq!(
-            ALIGNER.align(b"AGCT", b"AGT"),
+            ALIGNER.align(b"AGCT", b"AGT").unwrap(),
             Alignment::from("AGCT", "AG_T", 2.0)
         )
     }
@@ -246,7 +220,7 @@ mod tests {
     #[test]
     fn test_deletion() {
         assert_eq!(
-            ALIGNER.align(b"AGT", b"AGCT"),
+            ALIGNER.align(b"AGT", b"AGCT").unwrap(),
             Alignment::from("AG_T", "AGCT", 2.0)
         )
     }
@@ -254,7 +228,7 @@ mod tests {
     #[test]
     fn test_double_insertion() {
         assert_eq!(
-            ALIGNER.align(b"AGCT", b"AT"),
+            ALIGNER.align(b"AGCT", b"AT").unwrap(),
             Alignment::from("AGCT", "A__T", 0.0)
         )
     }
@@ -262,7 +236,7 @@ mod tests {
     #[test]
     fn test_double_deletion() {
         assert_eq!(
-            ALIGNER.align(b"AT", b"AGCT"),
+            ALIGNER.align(b"AT", b"AGCT").unwrap(),
             Alignment::from("A__T", "AGCT", 0.0)
         )
     }
@@ -270,7 +244,7 @@ mod tests {
     #[test]
     fn test_leading_insertion() {
         assert_eq!(
-            ALIGNER.align(b"AGCT", b"GCT"),
+            ALIGNER.align(b"AGCT", b"GCT").unwrap(),
             Alignment::from("AGCT", "_GCT", 2.0)
         )
     }
@@ -278,7 +252,7 @@ mod tests {
     #[test]
     fn test_leading_deletion() {
         assert_eq!(
-            ALIGNER.align(b"GCT", b"AGCT"),
+            ALIGNER.align(b"GCT", b"AGCT").unwrap(),
             Alignment::from("_GCT", "AGCT", 2.0)
         )
     }
@@ -286,7 +260,7 @@ mod tests {
     #[test]
     fn test_trailing_insertion() {
         assert_eq!(
-            ALIGNER.align(b"AGCT", b"AGC"),
+            ALIGNER.align(b"AGCT", b"AGC").unwrap(),
             Alignment::from("AGCT", "AGC_", 2.0)
         )
     }
@@ -294,7 +268,7 @@ mod tests {
     #[test]
     fn test_trailing_deletion() {
         assert_eq!(
-            ALIGNER.align(b"AGC", b"AGCT"),
+            ALIGNER.align(b"AGC", b"AGCT").unwrap(),
             Alignment::from("AGC_", "AGCT", 2.0)
         )
     }
@@ -302,7 +276,7 @@ mod tests {
     #[test]
     fn test_two_insertions() {
         assert_eq!(
-            ALIGNER.align(b"AGCT", b"GT"),
+            ALIGNER.align(b"AGCT", b"GT").unwrap(),
             Alignment::from("AGCT", "_G_T", 0.0)
         )
     }
@@ -310,7 +284,7 @@ mod tests {
     #[test]
     fn test_two_deletions() {
         assert_eq!(
-            ALIGNER.align(b"AC", b"AGCT"),
+            ALIGNER.align(b"AC", b"AGCT").unwrap(),
             Alignment::from("A_C_", "AGCT", 0.0)
         )
     }
@@ -319,7 +293,7 @@ mod tests {
     fn test_empty_subject() {
         assert_eq!(
             ALIGNER.align(b"", b"AGCT"),
-            Alignment::from("____", "AGCT", -4.0)
+            Err(Error::EmptySequence(Sequence::Subject))
         )
     }
 
@@ -327,8 +301,397 @@ mod tests {
     fn test_empty_reference() {
         assert_eq!(
             ALIGNER.align(b"AGCT", b""),
-            Alignment::from("AGCT", "____", -4.0)
+            Err(Error::EmptySequence(Sequence::Reference))
+        )
+    }
+
+    #[test]
+    fn test_local_fill_floors_at_zero() {
+        let mut mtx = matrix::of(2, 2);
+        LOCAL_ALIGNER.fill(&mut mtx, b"A", b"C");
+        assert_eq!(
+            mtx[(1, 1)],
+            Element::default()
+        );
+    }
+
+    #[test]
+    fn test_local_end_idx() {
+        let mtx = matrix::from_elements(
+            &[
+                [Element::default(), Element::default(), Element::default()],
+                [Element::default(), substitution(2.0), substitution(1.0)]
+            ]
+        );
+        assert_eq!(
+            LOCAL_ALIGNER.end_idx(&mtx),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn test_local_trace_back_stops_at_zero() {
+        let mtx = matrix::from_elements(
+            &[
+                [Element::default(), Element::default(), Element::default()],
+                [Element::default(), Element::default(), substitution(1.0)]
+            ]
+        );
+        assert_eq!(
+            LOCAL_ALIGNER.trace_back(&mtx, (1, 2), b"A", b"CA").unwrap(),
+            Alignment::starting_at((0, 1), "A", "A", 1.0)
+        );
+    }
+
+    #[test]
+    fn test_local_subject_in_reference() {
+        let alignment = LOCAL_ALIGNER.align(b"AGCT", b"TTAGCTGG").unwrap();
+        assert_eq!(
+            alignment,
+            Alignment::starting_at((0, 2), "AGCT", "AGCT", 4.0)
+        );
+        assert_eq!(alignment.start(), (0, 2));
+        assert_eq!(alignment.end(), (4, 6));
+    }
+
+    #[test]
+    fn test_local_reference_in_subject() {
+        assert_eq!(
+            LOCAL_ALIGNER.align(b"GGAGCTCC", b"AGCT").unwrap(),
+            Alignment::starting_at((2, 0), "AGCT", "AGCT", 4.0)
+        )
+    }
+
+    #[test]
+    fn test_local_mismatch() {
+        assert_eq!(
+            LOCAL_ALIGNER.align(b"AGCTTAGCT", b"GGAGCTAAGCTGG").unwrap(),
+            Alignment::starting_at((0, 2), "AGCTTAGCT", "AGCTAAGCT", 7.0)
+        )
+    }
+
+    #[test]
+    fn test_local_deletion() {
+        assert_eq!(
+            LOCAL_ALIGNER.align(b"AGCTAGCT", b"TAGCTTAGCTA").unwrap(),
+            Alignment::starting_at((0, 1), "AGC_TAGCT", "AGCTTAGCT", 7.0)
+        )
+    }
+
+    #[test]
+    fn test_local_no_similarity() {
+        assert_eq!(
+            LOCAL_ALIGNER.align(b"AAAA", b"TTTT").unwrap(),
+            Alignment::starting_at((0, 0), "", "", 0.0)
+        )
+    }
+
+    #[test]
+    fn test_affine_fill_top_row() {
+        let mut mtx = matrix::of(1, 4);
+        AFFINE_ALIGNER.fill_top_row(&mut mtx);
+        assert_eq!(mtx[(0, 1)], deletion(-2.0));
+        assert_eq!(mtx[(0, 2)], deletion(-2.5));
+        assert_eq!(mtx[(0, 3)], deletion(-3.0));
+    }
+
+    #[test]
+    fn test_affine_fill_left_column() {
+        let mut mtx = matrix::of(4, 1);
+        AFFINE_ALIGNER.fill_left_column(&mut mtx);
+        assert_eq!(mtx[(1, 0)], insertion(-2.0));
+        assert_eq!(mtx[(2, 0)], insertion(-2.5));
+        assert_eq!(mtx[(3, 0)], insertion(-3.0));
+    }
+
+    #[test]
+    fn test_affine_fill_marks_extension() {
+        let mut mtx = matrix::of(4, 2);
+        AFFINE_ALIGNER.fill_top_row(&mut mtx);
+        AFFINE_ALIGNER.fill_left_column(&mut mtx);
+        AFFINE_ALIGNER.fill(&mut mtx, b"AGG", b"A");
+        assert_eq!(mtx[(2, 1)], insertion(-1.0));
+        assert_eq!(mtx[(3, 1)], Element { extends_insertion: true, ..insertion(-1.5) });
+    }
+
+    #[test]
+    fn test_affine_prefers_mismatches_to_gaps() {
+        assert_eq!(
+            AFFINE_ALIGNER.align(b"CTAG", b"CGTG").unwrap(),
+            Alignment::from("CTAG", "CGTG", 0.0)
+        );
+        assert_eq!(
+            ALIGNER.align(b"CTAG", b"CGTG").unwrap(),
+            Alignment::from("C_TAG", "CGT_G", 1.0)
+        )
+    }
+
+    #[test]
+    fn test_affine_long_insertion() {
+        assert_eq!(
+            AFFINE_ALIGNER.align(b"AAAGGGTTT", b"AAATTT").unwrap(),
+            Alignment::from("AAAGGGTTT", "AAA___TTT", 3.0)
+        )
+    }
+
+    #[test]
+    fn test_affine_long_deletion() {
+        assert_eq!(
+            AFFINE_ALIGNER.align(b"AAATTT", b"AAAGGGTTT").unwrap(),
+            Alignment::from("AAA___TTT", "AAAGGGTTT", 3.0)
+        )
+    }
+
+    #[test]
+    fn test_affine_merges_gaps() {
+        assert_eq!(
+            AFFINE_ALIGNER.align(b"GCTTGT", b"TTTA").unwrap(),
+            Alignment::from("GCTTGT", "__TTTA", -2.5)
+        )
+    }
+
+    #[test]
+    fn test_affine_leading_gap() {
+        assert_eq!(
+            AFFINE_ALIGNER.align(b"CGGTTAG", b"TTGC").unwrap(),
+            Alignment::from("CGGTTAG", "___TTGC", -3.0)
+        )
+    }
+
+    #[test]
+    fn test_affine_local_gap() {
+        assert_eq!(
+            AFFINE_LOCAL_ALIGNER.align(b"TTGCATGCATCCCGCATGCATTT", b"AAGCATGCATGCATGCATAA").unwrap(),
+            Alignment::starting_at((2, 2), "GCATGCATCCCGCATGCAT", "GCATGCAT___GCATGCAT", 13.0)
+        )
+    }
+
+    fn semi_global(free_end_gaps: FreeEndGaps) -> GlobalNtAligner {
+        GlobalNtAligner { config: CONFIG, free_end_gaps }
+    }
+
+    #[test]
+    fn test_free_leading_subject_gaps() {
+        let mut mtx = matrix::of(1, 3);
+        semi_global(FreeEndGaps::SUBJECT_IN_REFERENCE).fill_top_row(&mut mtx);
+        for i in 1..3 {
+            assert_eq!(
+                mtx[(0, i)],
+                deletion(0.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_free_leading_reference_gaps() {
+        let mut mtx = matrix::of(3, 1);
+        semi_global(FreeEndGaps::REFERENCE_IN_SUBJECT).fill_left_column(&mut mtx);
+        for i in 1..3 {
+            assert_eq!(
+                mtx[(i, 0)],
+                insertion(0.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_free_trailing_subject_gaps_end_idx() {
+        let mtx = matrix::from_elements(
+            &[
+                [Element::default(), deletion(-1.0), deletion(-2.0)],
+                [insertion(-1.0), substitution(1.0), deletion(0.0)]
+            ]
+        );
+        assert_eq!(semi_global(FreeEndGaps::NONE).end_idx(&mtx), (1, 2));
+        assert_eq!(semi_global(FreeEndGaps::SUBJECT_IN_REFERENCE).end_idx(&mtx), (1, 1));
+        assert_eq!(semi_global(FreeEndGaps::REFERENCE_IN_SUBJECT).end_idx(&mtx), (1, 2));
+    }
+
+    #[test]
+    fn test_trace_back_trailing_gaps() {
+        let mtx = matrix::from_elements(
+            &[
+                [Element::default(), deletion(0.0)],
+                [insertion(-1.0), substitution(1.0)],
+                [insertion(-2.0), insertion(0.0)]
+            ]
+        );
+        assert_eq!(
+            semi_global(FreeEndGaps::REFERENCE_IN_SUBJECT).trace_back(&mtx, (1, 1), b"AC", b"A").unwrap(),
+            Alignment::from("AC", "A_", 1.0)
+        );
+    }
+
+    #[test]
+    fn test_subject_in_reference() {
+        assert_eq!(
+            semi_global(FreeEndGaps::SUBJECT_IN_REFERENCE).align(b"AGCT", b"TTAGCTGG").unwrap(),
+            Alignment::from("__AGCT__", "TTAGCTGG", 4.0)
+        )
+    }
+
+    #[test]
+    fn test_subject_in_reference_with_mismatch() {
+        assert_eq!(
+            semi_global(FreeEndGaps::SUBJECT_IN_REFERENCE).align(b"AGGT", b"TTAGCTGG").unwrap(),
+            Alignment::from("__AGGT__", "TTAGCTGG", 2.0)
         )
     }
-}
 
+    #[test]
+    fn test_reference_in_subject() {
+        assert_eq!(
+            semi_global(FreeEndGaps::REFERENCE_IN_SUBJECT).align(b"GGAGCTCC", b"AGCT").unwrap(),
+            Alignment::from("GGAGCTCC", "__AGCT__", 4.0)
+        )
+    }
+
+    #[test]
+    fn test_overlap() {
+        assert_eq!(
+            semi_global(FreeEndGaps::OVERLAP).align(b"TTTTACGT", b"ACGTCCCC").unwrap(),
+            Alignment::from("TTTTACGT____", "____ACGTCCCC", 4.0)
+        )
+    }
+
+    #[test]
+    fn test_glocal_prefix() {
+        assert_eq!(
+            semi_global(FreeEndGaps::of(false, true, false, false)).align(b"AGCT", b"AGCTTTTT").unwrap(),
+            Alignment::from("AGCT____", "AGCTTTTT", 4.0)
+        )
+    }
+
+    #[test]
+    fn test_free_end_gaps_only_at_ends() {
+        assert_eq!(
+            semi_global(FreeEndGaps::SUBJECT_IN_REFERENCE).align(b"AGCT", b"TTAGGCTGG").unwrap(),
+            Alignment::from("__A_GCT__", "TTAGGCTGG", 3.0)
+        )
+    }
+
+    #[test]
+    fn test_score_agrees_with_alignment() {
+        let pairs: [(&[u8], &[u8]); 4] = [
+            (b"ACGTAGCTAGCT", b"AGCAGCTAGCT"),
+            (b"GCTTGT", b"TTTA"),
+            (b"CGGTTAG", b"TTGC"),
+            (b"AGCT", b"TTAGCTGG"),
+        ];
+        let global = [
+            ALIGNER,
+            AFFINE_ALIGNER,
+            semi_global(FreeEndGaps::SUBJECT_IN_REFERENCE),
+            semi_global(FreeEndGaps::REFERENCE_IN_SUBJECT),
+            semi_global(FreeEndGaps::OVERLAP),
+            GlobalNtAligner { config: AFFINE_CONFIG, free_end_gaps: FreeEndGaps::OVERLAP },
+        ];
+        for (subject, reference) in pairs.iter() {
+            for aligner in global.iter() {
+                assert_eq!(aligner.score(subject, reference).unwrap(), aligner.align(subject, reference).unwrap().score);
+            }
+            for aligner in [LOCAL_ALIGNER, AFFINE_LOCAL_ALIGNER].iter() {
+                assert_eq!(aligner.score(subject, reference).unwrap(), aligner.align(subject, reference).unwrap().score);
+            }
+        }
+    }
+
+    #[test]
+    fn test_workspace() {
+        let pairs: [(&[u8], &[u8]); 4] = [
+            (b"ACGTAGCTAGCTTTGACCA", b"AGCAGCTAGCTTGACA"),
+            (b"GCTTGT", b"TTTA"),
+            (b"CGGTTAGGATTACA", b"TTGC"),
+            (b"AGCT", b"TTAGCTGG"),
+        ];
+        let mut workspace = AlignerWorkspace::new();
+        for aligner in [AFFINE_ALIGNER, semi_global(FreeEndGaps::OVERLAP)].iter() {
+            for (subject, reference) in pairs.iter() {
+                assert_eq!(aligner.align_with(&mut workspace, subject, reference), aligner.align(subject, reference));
+            }
+        }
+        let capacity = workspace.capacity();
+        assert!(capacity >= 20 * 17);
+        for (subject, reference) in pairs.iter() {
+            assert_eq!(AFFINE_LOCAL_ALIGNER.align_with(&mut workspace, subject, reference), AFFINE_LOCAL_ALIGNER.align(subject, reference));
+        }
+        assert_eq!(workspace.capacity(), capacity);
+        assert_eq!(ALIGNER.align_with(&mut workspace, b"", b"ACGT"), Err(Error::EmptySequence(Sequence::Subject)));
+        assert_eq!(workspace.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_score_types() {
+        fn config<S: Score>(score: fn(i16) -> S) -> NtAlignmentConfig<S> {
+            NtAlignmentConfig {
+                match_score: score(2),
+                mismatch_penalty: score(-1),
+                subject_gap_penalty: score(-3),
+                subject_gap_extension_penalty: score(-1),
+                reference_gap_penalty: score(-2),
+                reference_gap_extension_penalty: score(-1),
+            }
+        }
+        let subject = b"ACGTTGCAAGTCCGATGCATTA";
+        let reference = b"GACGTTGCAGTCCGATGGCAT";
+        let expected = GlobalNtAligner::from(config(f64::from)).align(subject, reference).unwrap();
+        assert_eq!(GlobalNtAligner::from(config(i16::from)).align(subject, reference).unwrap(), expected);
+        assert_eq!(GlobalNtAligner::from(config(i32::from)).align(subject, reference).unwrap(), expected);
+        assert_eq!(GlobalNtAligner::from(config(f32::from)).align(subject, reference).unwrap(), expected);
+        let local = LocalNtAligner::from(config(i32::from));
+        assert_eq!(
+            local.align(subject, reference).unwrap(),
+            LocalNtAligner::from(config(f64::from)).align(subject, reference).unwrap()
+        );
+        assert_eq!(local.score(subject, reference).unwrap(), local.align(subject, reference).unwrap().score);
+        let banded = BandedNtAligner { config: config(i16::from), band: Band::around_main_diagonal(4) };
+        assert_eq!(banded.align(subject, reference).unwrap().alignment, expected);
+    }
+
+    #[test]
+    fn test_score_overflow() {
+        fn config<S: Score>(score: fn(i16) -> S) -> NtAlignmentConfig<S> {
+            NtAlignmentConfig {
+                match_score: score(1),
+                mismatch_penalty: score(-1),
+                subject_gap_penalty: score(-2),
+                subject_gap_extension_penalty: score(-2),
+                reference_gap_penalty: score(-2),
+                reference_gap_extension_penalty: score(-2),
+            }
+        }
+        let subject = vec![b'A'; 20_000];
+        let reference = vec![b'A'; 20];
+        let global = GlobalNtAligner::from(config(i16::from));
+        assert_eq!(global.align(&subject, &reference), Err(Error::ScoreOverflow));
+        assert_eq!(global.score(&subject, &reference), Err(Error::ScoreOverflow));
+        assert_eq!(global.align_in_linear_space(&subject, &reference), Err(Error::ScoreOverflow));
+        assert_eq!(LocalNtAligner::from(config(i16::from)).score(&subject, &reference), Ok(20.0));
+        let wide = GlobalNtAligner::from(config(i32::from));
+        assert_eq!(wide.score(&subject, &reference), Ok(20.0 - 2.0 * 19_980.0));
+    }
+
+    #[test]
+    fn test_invalid_symbol() {
+        assert!(ALIGNER.align(b"acgu", b"ACNT").is_ok());
+        assert_eq!(
+            ALIGNER.align(b"ACGT", b"AC-T"),
+            Err(Error::InvalidSymbol { sequence: Sequence::Reference, position: 2, symbol: b'-' })
+        );
+        assert_eq!(
+            ALIGNER.score(b"AC GT", b"ACGT"),
+            Err(Error::InvalidSymbol { sequence: Sequence::Subject, position: 2, symbol: b' ' })
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let positive_gap = GlobalNtAligner::from(NtAlignmentConfig { reference_gap_penalty: 1.0, ..AFFINE_CONFIG });
+        assert_eq!(
+            positive_gap.align(b"ACGT", b"ACGT"),
+            Err(Error::InvalidConfig { parameter: "reference gap penalty", value: 1.0 })
+        );
+        let nan_match = LocalNtAligner::from(NtAlignmentConfig { match_score: FScore::NAN, ..AFFINE_CONFIG });
+        assert!(nan_match.align_in_linear_space(b"ACGT", b"ACGT").is_err());
+    }
+}
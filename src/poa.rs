@@ -0,0 +1,454 @@
+use crate::aligner::validate;
+use crate::config::AlignmentConfig;
+use crate::element::{FScore, Score};
+use crate::error::{check_end_score, Error, Result, MAX_MATRIX_CELLS};
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    symbol: u8,
+    predecessors: Vec<usize>,
+    /// Successors with the number of sequences passing from this node to them.
+    successors: Vec<(usize, usize)>,
+}
+
+/// Partial-order alignment graph (Lee et al., 2002): a DAG of symbols in which every added
+/// sequence is a path, sharing the nodes it matches and branching where it differs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoaGraph {
+    nodes: Vec<Node>,
+    /// The nodes in topological order.
+    order: Vec<usize>,
+    sequences: usize,
+}
+
+/// An alignment of a sequence to a graph: each pair holds a node, a 0-based sequence
+/// position or both, in the order of the sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphAlignment {
+    pub score: FScore,
+    pub pairs: Vec<(Option<usize>, Option<usize>)>,
+}
+
+impl PoaGraph {
+    pub fn new() -> Self {
+        PoaGraph::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The number of sequences added.
+    pub fn sequences(&self) -> usize {
+        self.sequences
+    }
+
+    pub fn symbol(&self, node: usize) -> u8 {
+        self.nodes[node].symbol
+    }
+
+    /// Adds `sequence` along its alignment to the graph, reusing the nodes it matches.
+    pub fn add(&mut self, sequence: &[u8], alignment: &GraphAlignment) {
+        let mut previous: Option<usize> = None;
+        for &pair in alignment.pairs.iter() {
+            let node = match pair {
+                (Some(node), Some(position)) if self.nodes[node].symbol.eq_ignore_ascii_case(&sequence[position]) => node,
+                (_, Some(position)) => self.add_node(sequence[position]),
+                (_, None) => continue,
+            };
+            if let Some(from) = previous {
+                self.add_edge(from, node);
+            }
+            previous = Some(node);
+        }
+        self.sequences += 1;
+        self.sort();
+    }
+
+    /// The heaviest path through the graph: every node is reached over its heaviest incoming
+    /// edge, ties going to the heavier predecessor, and the path ends at the heaviest node.
+    pub fn consensus(&self) -> Vec<u8> {
+        let mut weights = vec![0; self.len()];
+        let mut previous: Vec<Option<usize>> = vec![None; self.len()];
+        for &node in self.order.iter() {
+            for &predecessor in self.nodes[node].predecessors.iter() {
+                let edge = self.edge_weight(predecessor, node);
+                let better = match previous[node] {
+                    None => true,
+                    Some(best) => {
+                        let best_edge = self.edge_weight(best, node);
+                        edge > best_edge || (edge == best_edge && weights[predecessor] > weights[best])
+                    }
+                };
+                if better {
+                    previous[node] = Some(predecessor);
+                    weights[node] = edge + weights[predecessor];
+                }
+            }
+        }
+        let mut cursor = self.order.iter()
+            .copied()
+            .fold(None, |best: Option<usize>, node| match best {
+                Some(best) if weights[best] >= weights[node] => Some(best),
+                _ => Some(node)
+            });
+        let mut consensus = Vec::new();
+        while let Some(node) = cursor {
+            consensus.push(self.nodes[node].symbol);
+            cursor = previous[node];
+        }
+        consensus.reverse();
+        consensus
+    }
+
+    /// Writes the graph as GFA 1.0, a segment per node and a link per edge, with the number
+    /// of sequences along each edge as its `RC` tag.
+    pub fn write_gfa<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "H\tVN:Z:1.0")?;
+        for &node in self.order.iter() {
+            writeln!(out, "S\t{}\t{}", node, self.nodes[node].symbol as char)?;
+        }
+        for &node in self.order.iter() {
+            for &(successor, weight) in self.nodes[node].successors.iter() {
+                writeln!(out, "L\t{}\t+\t{}\t+\t0M\tRC:i:{}", node, successor, weight)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the graph in the Graphviz DOT language, the edges labelled with their weights.
+    pub fn write_dot<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "digraph poa {{")?;
+        writeln!(out, "    rankdir=LR;")?;
+        for &node in self.order.iter() {
+            writeln!(out, "    {} [label=\"{}\"];", node, self.nodes[node].symbol as char)?;
+        }
+        for &node in self.order.iter() {
+            for &(successor, weight) in self.nodes[node].successors.iter() {
+                writeln!(out, "    {} -> {} [label=\"{}\"];", node, successor, weight)?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    fn add_node(&mut self, symbol: u8) -> usize {
+        self.nodes.push(Node { symbol, predecessors: Vec::new(), successors: Vec::new() });
+        self.nodes.len() - 1
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize) {
+        match self.nodes[from].successors.iter_mut().find(|(successor, _)| *successor == to) {
+            Some((_, weight)) => *weight += 1,
+            None => {
+                self.nodes[from].successors.push((to, 1));
+                self.nodes[to].predecessors.push(from);
+            }
+        }
+    }
+
+    fn edge_weight(&self, from: usize, to: usize) -> usize {
+        self.nodes[from].successors.iter()
+            .find(|(successor, _)| *successor == to)
+            .map_or(0, |&(_, weight)| weight)
+    }
+
+    /// Orders the nodes topologically (Kahn, 1962).
+    fn sort(&mut self) {
+        let mut incoming: Vec<usize> = self.nodes.iter().map(|node| node.predecessors.len()).collect();
+        let mut ready: Vec<usize> = (0..self.len()).rev().filter(|&node| incoming[node] == 0).collect();
+        self.order.clear();
+        while let Some(node) = ready.pop() {
+            self.order.push(node);
+            for &(successor, _) in self.nodes[node].successors.iter().rev() {
+                incoming[successor] -= 1;
+                if incoming[successor] == 0 {
+                    ready.push(successor);
+                }
+            }
+        }
+    }
+}
+
+/// Aligns sequences globally to a `PoaGraph` with affine gaps. The sequence is the subject
+/// and the graph the reference; positions passed to the configuration are those of the
+/// sequence, and the reference position is always 1.
+pub struct PoaAligner<C> {
+    pub config: C,
+}
+
+impl<C: AlignmentConfig> From<C> for PoaAligner<C> {
+    fn from(config: C) -> Self {
+        PoaAligner { config }
+    }
+}
+
+/// The score matrices of a graph alignment, a row per node in topological order after
+/// a row for the start, and a column per sequence prefix.
+struct Scores<S> {
+    cols: usize,
+    best: Vec<S>,
+    /// Ending in a gap in the sequence.
+    deletion: Vec<S>,
+    /// Ending in a gap in the graph.
+    insertion: Vec<S>,
+}
+
+impl<C: AlignmentConfig> PoaAligner<C> {
+    /// Aligns `sequence` to the graph and adds it, returning the alignment it was added along.
+    pub fn add(&self, graph: &mut PoaGraph, sequence: &[u8]) -> Result<GraphAlignment> {
+        let alignment = self.align(graph, sequence)?;
+        graph.add(sequence, &alignment);
+        Ok(alignment)
+    }
+
+    /// The sequence takes the subject positions of the scoring; the graph has no reference
+    /// positions, so scoring made for one reference, as a profile, is refused.
+    pub fn align(&self, graph: &PoaGraph, sequence: &[u8]) -> Result<GraphAlignment> {
+        let config = &self.config;
+        if let Some(length) = config.reference_length() {
+            return Err(Error::InvalidConfig { parameter: "reference length", value: length as FScore });
+        }
+        validate(config, sequence, sequence)?;
+        let (rows, cols) = (graph.len() + 1, sequence.len() + 1);
+        match cols.checked_mul(rows) {
+            Some(cells) if cells <= MAX_MATRIX_CELLS => {}
+            _ => return Err(Error::TooLarge { subject_length: sequence.len(), reference_length: graph.len() })
+        }
+        let predecessors = self.predecessor_rows(graph);
+        let scores = self.fill(graph, &predecessors, sequence);
+        let end = |row: usize| scores.best[row * cols + cols - 1];
+        let last_row = (1..rows)
+            .filter(|&row| graph.nodes[graph.order[row - 1]].successors.is_empty())
+            .fold(0, |best, row| if best == 0 || end(row) > end(best) { row } else { best });
+        let score = check_end_score(end(last_row))?;
+        let pairs = self.trace_back(graph, &predecessors, &scores, sequence, last_row);
+        Ok(GraphAlignment { score, pairs })
+    }
+
+    /// By row, the rows of the predecessors of its node, the start row for nodes without any.
+    fn predecessor_rows(&self, graph: &PoaGraph) -> Vec<Vec<usize>> {
+        let mut rank = vec![0; graph.len()];
+        for (i, &node) in graph.order.iter().enumerate() {
+            rank[node] = i + 1;
+        }
+        let mut rows = vec![Vec::new()];
+        for &node in graph.order.iter() {
+            let predecessors = &graph.nodes[node].predecessors;
+            rows.push(if predecessors.is_empty() { vec![0] } else { predecessors.iter().map(|&p| rank[p]).collect() });
+        }
+        rows
+    }
+
+    fn fill(&self, graph: &PoaGraph, predecessors: &[Vec<usize>], sequence: &[u8]) -> Scores<C::Score> {
+        let config = &self.config;
+        let cols = sequence.len() + 1;
+        let cells = predecessors.len() * cols;
+        let mut scores = Scores {
+            cols,
+            best: vec![C::Score::UNREACHABLE; cells],
+            deletion: vec![C::Score::UNREACHABLE; cells],
+            insertion: vec![C::Score::UNREACHABLE; cells],
+        };
+        scores.best[0] = C::Score::ZERO;
+        for j in 1..cols {
+            scores.insertion[j] = self.insertion(&scores, j);
+            scores.best[j] = scores.insertion[j];
+        }
+        for (row, rows) in predecessors.iter().enumerate().skip(1) {
+            let symbol = graph.nodes[graph.order[row - 1]].symbol;
+            for j in 0..cols {
+                let cell = row * cols + j;
+                let mut substitution = C::Score::UNREACHABLE;
+                let mut deletion = C::Score::UNREACHABLE;
+                for &p in rows.iter() {
+                    deletion = deletion
+                        .max(scores.best[p * cols + j].plus(config.get_subject_gap_opening_penalty(1)))
+                        .max(scores.deletion[p * cols + j].plus(config.get_subject_gap_extension_penalty(1)));
+                    if j > 0 {
+                        substitution = substitution
+                            .max(scores.best[p * cols + j - 1].plus(config.get_substitution_score((j, 1), sequence[j - 1], symbol)));
+                    }
+                }
+                scores.deletion[cell] = deletion;
+                if j > 0 {
+                    scores.insertion[cell] = self.insertion(&scores, cell);
+                }
+                scores.best[cell] = substitution.max(deletion).max(scores.insertion[cell]);
+            }
+        }
+        scores
+    }
+
+    /// The score of ending `cell` in a gap in the graph, from the cell to its left.
+    fn insertion(&self, scores: &Scores<C::Score>, cell: usize) -> C::Score {
+        let j = cell % scores.cols;
+        scores.best[cell - 1].plus(self.config.get_reference_gap_opening_penalty(j))
+            .max(scores.insertion[cell - 1].plus(self.config.get_reference_gap_extension_penalty(j)))
+    }
+
+    fn trace_back(
+        &self,
+        graph: &PoaGraph,
+        predecessors: &[Vec<usize>],
+        scores: &Scores<C::Score>,
+        sequence: &[u8],
+        last_row: usize,
+    ) -> Vec<(Option<usize>, Option<usize>)> {
+        #[derive(PartialEq)]
+        enum State { Best, Deletion, Insertion }
+        let config = &self.config;
+        let cols = scores.cols;
+        let node = |row: usize| graph.order[row - 1];
+        let mut pairs = Vec::new();
+        let (mut row, mut j) = (last_row, cols - 1);
+        let mut state = State::Best;
+        while row > 0 || j > 0 {
+            let cell = row * cols + j;
+            match state {
+                State::Best => {
+                    let substitution = if row > 0 && j > 0 {
+                        let score = config.get_substitution_score((j, 1), sequence[j - 1], graph.nodes[node(row)].symbol);
+                        predecessors[row].iter().copied().find(|&p| scores.best[p * cols + j - 1].plus(score) == scores.best[cell])
+                    } else {
+                        None
+                    };
+                    if let Some(p) = substitution {
+                        pairs.push((Some(node(row)), Some(j - 1)));
+                        row = p;
+                        j -= 1;
+                    } else if row > 0 && scores.deletion[cell] == scores.best[cell] {
+                        state = State::Deletion;
+                    } else {
+                        state = State::Insertion;
+                    }
+                }
+                State::Deletion => {
+                    pairs.push((Some(node(row)), None));
+                    let opening = predecessors[row].iter().copied()
+                        .find(|&p| scores.best[p * cols + j].plus(config.get_subject_gap_opening_penalty(1)) == scores.deletion[cell]);
+                    match opening {
+                        Some(p) => {
+                            row = p;
+                            state = State::Best;
+                        }
+                        None => {
+                            row = predecessors[row].iter().copied()
+                                .find(|&p| scores.deletion[p * cols + j].plus(config.get_subject_gap_extension_penalty(1)) == scores.deletion[cell])
+                                .unwrap_or(0);
+                        }
+                    }
+                }
+                State::Insertion => {
+                    pairs.push((None, Some(j - 1)));
+                    if scores.best[cell - 1].plus(config.get_reference_gap_opening_penalty(j)) == scores.insertion[cell] {
+                        state = State::Best;
+                    }
+                    j -= 1;
+                }
+            }
+        }
+        pairs.reverse();
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::{Aligner, GlobalAligner};
+    use crate::error::{Error, Sequence};
+    use crate::nt_aligner::NtAlignmentConfig;
+    use crate::poa::{PoaAligner, PoaGraph};
+    use crate::profile::ProfileConfig;
+    use crate::quality::QualityConfig;
+
+    const CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 2.0,
+        mismatch_penalty: -3.0,
+        subject_gap_penalty: -5.0,
+        subject_gap_extension_penalty: -2.0,
+        reference_gap_penalty: -5.0,
+        reference_gap_extension_penalty: -2.0,
+    };
+
+    fn graph(sequences: &[&[u8]]) -> PoaGraph {
+        let aligner = PoaAligner::from(CONFIG);
+        let mut graph = PoaGraph::new();
+        for sequence in sequences.iter() {
+            aligner.add(&mut graph, sequence).unwrap();
+        }
+        graph
+    }
+
+    #[test]
+    fn test_linear_graph() {
+        let graph = graph(&[b"ACGTTGCAAGTC"]);
+        assert_eq!(graph.len(), 12);
+        assert_eq!(graph.consensus(), b"ACGTTGCAAGTC".to_vec());
+        let subject = b"ACGTGCAAGGTC";
+        let alignment = PoaAligner::from(CONFIG).align(&graph, subject).unwrap();
+        assert_eq!(alignment.score, GlobalAligner::from(CONFIG).align(subject, b"ACGTTGCAAGTC").unwrap().score);
+        assert_eq!(alignment.pairs.iter().filter(|pair| pair.0.is_some() && pair.1.is_some()).count(), 11);
+    }
+
+    #[test]
+    fn test_consensus() {
+        let truth = b"ACGTTGCAAGTCCGATGCATTGCA";
+        let graph = graph(&[
+            b"ACGTTGCAAGTCCGATGCATTGCA",
+            b"ACGTTGCTAGTCCGATGCATTGCA",
+            b"ACGTTGCAAGTCCGAATGCATTGCA",
+            b"ACGTTGCAAGTCGATGCATTGCA",
+            b"ACGTTGCAAGTCCGATGCATTCCA",
+        ]);
+        assert_eq!(graph.sequences(), 5);
+        assert_eq!(graph.consensus(), truth.to_vec());
+    }
+
+    #[test]
+    fn test_write() {
+        let graph = graph(&[b"ACG", b"AGG"]);
+        let mut gfa = Vec::new();
+        graph.write_gfa(&mut gfa).unwrap();
+        assert_eq!(
+            String::from_utf8(gfa).unwrap(),
+            "H\tVN:Z:1.0\nS\t0\tA\nS\t1\tC\nS\t3\tG\nS\t2\tG\n\
+             L\t0\t+\t1\t+\t0M\tRC:i:1\nL\t0\t+\t3\t+\t0M\tRC:i:1\n\
+             L\t1\t+\t2\t+\t0M\tRC:i:1\nL\t3\t+\t2\t+\t0M\tRC:i:1\n"
+        );
+        let mut dot = Vec::new();
+        graph.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph poa {\n    rankdir=LR;\n    0 [label=\"A\"];\n"));
+        assert!(dot.contains("    3 -> 2 [label=\"1\"];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let aligner = PoaAligner::from(CONFIG);
+        assert_eq!(
+            aligner.align(&PoaGraph::new(), b"ACXT"),
+            Err(Error::InvalidSymbol { sequence: Sequence::Subject, position: 2, symbol: b'X' })
+        );
+        assert_eq!(aligner.align(&PoaGraph::new(), b""), Err(Error::EmptySequence(Sequence::Subject)));
+    }
+
+    #[test]
+    fn test_position_specific_scoring() {
+        assert_eq!(
+            PoaAligner::from(QualityConfig::new(&CONFIG, b"II")).align(&PoaGraph::new(), b"ACGT"),
+            Err(Error::SequenceLength { sequence: Sequence::Subject, expected: 2, found: 4 })
+        );
+        assert!(PoaAligner::from(QualityConfig::new(&CONFIG, b"IIII")).align(&graph(&[b"ACGT"]), b"ACGT").is_ok());
+        let profile = ProfileConfig::from_sequence(b"ACGT", b"ACGT", &CONFIG);
+        assert_eq!(
+            PoaAligner::from(profile).align(&graph(&[b"ACGT"]), b"ACGT"),
+            Err(Error::InvalidConfig { parameter: "reference length", value: 4.0 })
+        );
+    }
+}
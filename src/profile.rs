@@ -0,0 +1,430 @@
+use crate::aligner::{GlobalAligner, LocalAligner};
+use crate::banded::BandedAligner;
+use crate::config::AlignmentConfig;
+use crate::element::FScore;
+use crate::error::{Result, check_penalty, check_score};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+const ABSENT: usize = usize::MAX;
+
+/// Subject gap penalty that keeps a reference position from being skipped, e.g. in a masked
+/// region. Aligning sequences that cannot be aligned without such a gap fails with
+/// `Error::NoAlignment`.
+pub const FORBIDDEN: FScore = FScore::NEG_INFINITY;
+
+/// Position-specific scoring of subjects against one reference, by a column per reference
+/// position. Only subject gaps have penalties per column: gaps in the reference, inserting
+/// subject symbols, cost the reference gap penalties everywhere, as `AlignmentConfig` looks
+/// their penalties up by subject position. Masks and homopolymer penalties leave them alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileConfig {
+    alphabet: Vec<u8>,
+    index: Vec<usize>,
+    columns: Vec<ProfileColumn>,
+    pub reference_gap_penalty: FScore,
+    pub reference_gap_extension_penalty: FScore,
+}
+
+/// The scores of the subject symbols against one reference position, and the penalties
+/// of subject gaps skipping it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileColumn {
+    /// By symbol, in the order of the profile alphabet.
+    pub scores: Vec<FScore>,
+    pub gap_penalty: FScore,
+    pub gap_extension_penalty: FScore,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProfileParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ProfileParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ProfileParseError {}
+
+impl ProfileConfig {
+    /// The profile scoring every subject symbol of `alphabet` against `reference` as `config` does.
+    /// Parts of `config` that depend on the subject position are taken at its first position.
+    pub fn from_sequence<C>(reference: &[u8], alphabet: &[u8], config: &C) -> Self
+        where C: AlignmentConfig<Score=FScore> {
+        let columns = reference.iter()
+            .enumerate()
+            .map(|(i, &r)| ProfileColumn {
+                scores: alphabet.iter().map(|&s| config.get_substitution_score((1, i + 1), s, r)).collect(),
+                gap_penalty: config.get_subject_gap_opening_penalty(i + 1),
+                gap_extension_penalty: config.get_subject_gap_extension_penalty(i + 1),
+            })
+            .collect();
+        Self::of(
+            alphabet.to_vec(),
+            columns,
+            config.get_reference_gap_opening_penalty(1),
+            config.get_reference_gap_extension_penalty(1),
+        )
+    }
+
+    /// Reads a profile in the text format `FromStr` parses.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn alphabet(&self) -> &[u8] {
+        &self.alphabet
+    }
+
+    /// The column of a 0-based reference position.
+    pub fn column(&self, position: usize) -> &ProfileColumn {
+        &self.columns[position]
+    }
+
+    pub fn score(&self, position: usize, symbol: u8) -> FScore {
+        self.columns[position].scores[self.index[symbol as usize]]
+    }
+
+    /// Sets the penalties of subject gaps over a range of 0-based reference positions.
+    pub fn set_gap_penalties(&mut self, positions: Range<usize>, opening: FScore, extension: FScore) {
+        for column in &mut self.columns[positions] {
+            column.gap_penalty = opening;
+            column.gap_extension_penalty = extension;
+        }
+    }
+
+    /// Keeps subject gaps out of a range of 0-based reference positions. Subject symbols may
+    /// still be inserted between them, at the reference gap penalties.
+    pub fn mask(&mut self, positions: Range<usize>) {
+        self.set_gap_penalties(positions, FORBIDDEN, FORBIDDEN)
+    }
+
+    /// Sets the penalties of subject gaps within the runs of at least `min_length` equal symbols
+    /// of `reference`, where sequencing errors tend to drop bases. Bases added to such runs are
+    /// insertions and keep the reference gap penalties.
+    pub fn set_homopolymer_gap_penalties(&mut self, reference: &[u8], min_length: usize, opening: FScore, extension: FScore) {
+        let mut start = 0;
+        for end in 1..=reference.len() {
+            if end == reference.len() || !reference[end].eq_ignore_ascii_case(&reference[start]) {
+                if end - start >= min_length {
+                    self.set_gap_penalties(start..end, opening, extension);
+                }
+                start = end;
+            }
+        }
+    }
+
+    fn of(alphabet: Vec<u8>, columns: Vec<ProfileColumn>, reference_gap_penalty: FScore, reference_gap_extension_penalty: FScore) -> Self {
+        let mut index = vec![ABSENT; 256];
+        alphabet.iter()
+            .enumerate()
+            .for_each(|(i, &symbol)| {
+                index[symbol.to_ascii_uppercase() as usize] = i;
+                index[symbol.to_ascii_lowercase() as usize] = i;
+            });
+        ProfileConfig { alphabet, index, columns, reference_gap_penalty, reference_gap_extension_penalty }
+    }
+}
+
+impl AlignmentConfig for ProfileConfig {
+    type Score = FScore;
+
+    fn get_substitution_score(&self, (_, col): (usize, usize), s: u8, _r: u8) -> FScore {
+        self.score(col - 1, s)
+    }
+    fn get_subject_gap_opening_penalty(&self, pos: usize) -> FScore {
+        self.columns[pos - 1].gap_penalty
+    }
+    fn get_reference_gap_opening_penalty(&self, _pos: usize) -> FScore {
+        self.reference_gap_penalty
+    }
+    fn get_subject_gap_extension_penalty(&self, pos: usize) -> FScore {
+        self.columns[pos - 1].gap_extension_penalty
+    }
+    fn get_reference_gap_extension_penalty(&self, _pos: usize) -> FScore {
+        self.reference_gap_extension_penalty
+    }
+    fn accepts(&self, symbol: u8) -> bool {
+        self.index[symbol as usize] != ABSENT
+    }
+    fn reference_length(&self) -> Option<usize> {
+        Some(self.columns.len())
+    }
+    fn validate(&self) -> Result<()> {
+        for column in self.columns.iter() {
+            for &score in column.scores.iter() {
+                check_score("profile score", score)?;
+            }
+            check_penalty("profile gap penalty", column.gap_penalty)?;
+            check_penalty("profile gap extension penalty", column.gap_extension_penalty)?;
+        }
+        check_penalty("reference gap penalty", self.reference_gap_penalty)?;
+        check_penalty("reference gap extension penalty", self.reference_gap_extension_penalty)
+    }
+}
+
+/// Parses a profile of the form
+///
+/// ```text
+/// # Comment
+/// reference_gap -5 -2
+///  A  C  G  T
+///  2 -3 -3 -3  -5 -2
+///  2 -3 -3 -3  -inf -inf
+/// ```
+///
+/// The `reference_gap` line holds the opening and extension penalties of reference gaps and the
+/// header the subject symbols. Each further line is a reference position: a score per symbol,
+/// then the opening and extension penalties of subject gaps skipping it.
+impl FromStr for ProfileConfig {
+    type Err = ProfileParseError;
+
+    fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
+        let mut lines = text.lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let (gap_line, gaps) = lines.next()
+            .ok_or_else(|| parse_error(0, "no reference gap penalties"))?;
+        let reference_gap = match gaps.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["reference_gap", values @ ..] => values.iter()
+                .map(|f| value(gap_line, f))
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+            _ => return Err(parse_error(gap_line, "expected reference_gap"))
+        };
+        if reference_gap.len() != 2 {
+            return Err(parse_error(gap_line, &format!("expected 2 reference gap penalties, found {}", reference_gap.len())));
+        }
+        let (header_line, header) = lines.next()
+            .ok_or_else(|| parse_error(gap_line, "no symbol header"))?;
+        let alphabet: Vec<u8> = header.split_whitespace()
+            .map(|field| match field.as_bytes() {
+                [symbol] => Ok(symbol.to_ascii_uppercase()),
+                _ => Err(parse_error(header_line, &format!("invalid symbol {}", field)))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        let mut columns = Vec::new();
+        for (n, line) in lines {
+            let mut values: Vec<FScore> = line.split_whitespace()
+                .map(|f| value(n, f))
+                .collect::<std::result::Result<_, _>>()?;
+            if values.len() != alphabet.len() + 2 {
+                return Err(parse_error(n, &format!("expected {} values, found {}", alphabet.len() + 2, values.len())));
+            }
+            let gap_extension_penalty = values.pop().unwrap_or_default();
+            let gap_penalty = values.pop().unwrap_or_default();
+            columns.push(ProfileColumn { scores: values, gap_penalty, gap_extension_penalty });
+        }
+        if columns.is_empty() {
+            return Err(parse_error(header_line, "no positions"));
+        }
+        Ok(ProfileConfig::of(alphabet, columns, reference_gap[0], reference_gap[1]))
+    }
+}
+
+fn value(line: usize, field: &str) -> std::result::Result<FScore, ProfileParseError> {
+    field.parse().map_err(|_| parse_error(line, &format!("invalid value {}", field)))
+}
+
+fn parse_error(line: usize, message: &str) -> ProfileParseError {
+    ProfileParseError { line, message: message.to_string() }
+}
+
+pub type GlobalProfileAligner = GlobalAligner<ProfileConfig>;
+
+pub type LocalProfileAligner = LocalAligner<ProfileConfig>;
+
+pub type BandedProfileAligner = BandedAligner<ProfileConfig>;
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::Aligner;
+    use crate::alignment::Alignment;
+    use crate::banded::Band;
+    use crate::element::{FScore, Op};
+    use crate::error::{Error, Sequence};
+    use crate::nt_aligner::{GlobalNtAligner, NtAlignmentConfig};
+    use crate::profile::{BandedProfileAligner, GlobalProfileAligner, LocalProfileAligner, ProfileConfig, ProfileParseError, FORBIDDEN};
+
+    const CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -5.0,
+        subject_gap_extension_penalty: -2.0,
+        reference_gap_penalty: -5.0,
+        reference_gap_extension_penalty: -2.0,
+    };
+
+    fn profile(reference: &[u8]) -> ProfileConfig {
+        ProfileConfig::from_sequence(reference, b"ACGT", &CONFIG)
+    }
+
+    fn deleted_positions(alignment: &Alignment) -> Vec<usize> {
+        alignment.anchors.iter()
+            .filter(|a| a.op == Op::DELETE)
+            .map(|a| a.idx.1 - 1)
+            .collect()
+    }
+
+    #[test]
+    fn test_same_as_config() {
+        let pairs: [(&[u8], &[u8]); 3] = [
+            (b"ACGTTGCAAGTCCGATGCAT", b"ACGTTGCAGTCCGATGGCAT"),
+            (b"GATTACA", b"CCGATTCACC"),
+            (b"ACGTACGT", b"TTTTACGTACGTTTTT"),
+        ];
+        for &(subject, reference) in pairs.iter() {
+            let expected = GlobalNtAligner::from(CONFIG).align(subject, reference).unwrap();
+            let aligner = GlobalProfileAligner::from(profile(reference));
+            assert_eq!(aligner.score(subject, reference), Ok(expected.score));
+            assert_eq!(aligner.align_in_linear_space(subject, reference).unwrap().score, expected.score);
+            assert_eq!(aligner.align(subject, reference), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn test_position_specific_scores() {
+        let mut config = profile(b"ACGT");
+        config.columns[1].scores = vec![-1.0, 1.0, 1.0, -1.0];
+        let aligner = GlobalProfileAligner::from(config);
+        assert_eq!(aligner.align(b"AGGT", b"ACGT"), Ok(Alignment::from("AGGT", "ACGT", 4.0)));
+        assert_eq!(aligner.align(b"AAGT", b"ACGT").unwrap().score, 2.0);
+    }
+
+    #[test]
+    fn test_homopolymer_gaps() {
+        let reference = b"ACGTTTTTTGCA";
+        let subject = b"ACGTTTTTGCA";
+        let mut config = profile(reference);
+        config.set_homopolymer_gap_penalties(reference, 4, -1.0, -1.0);
+        assert_eq!(config.column(2).gap_penalty, -5.0);
+        assert_eq!(config.column(3).gap_penalty, -1.0);
+        assert_eq!(config.column(8).gap_extension_penalty, -1.0);
+        assert_eq!(config.column(9).gap_penalty, -5.0);
+        let alignment = GlobalProfileAligner::from(config).align(subject, reference).unwrap();
+        assert_eq!(alignment.score, 10.0);
+        assert!(deleted_positions(&alignment).iter().all(|p| (3..9).contains(p)));
+        assert_eq!(GlobalNtAligner::from(CONFIG).score(subject, reference), Ok(6.0));
+    }
+
+    #[test]
+    fn test_masked_gaps() {
+        let reference = b"AACCGGTTAACCGGTT";
+        let subject = b"AACCGTTAACCGGTT";
+        let mut config = profile(reference);
+        let unmasked = GlobalProfileAligner::from(config.clone()).align(subject, reference).unwrap();
+        assert!(deleted_positions(&unmasked).iter().all(|p| (4..6).contains(p)));
+        config.mask(2..10);
+        let aligner = GlobalProfileAligner::from(config);
+        let masked = aligner.align(subject, reference).unwrap();
+        assert!(masked.score < unmasked.score);
+        assert!(deleted_positions(&masked).iter().all(|p| !(2..10).contains(p)));
+        assert_eq!(aligner.score(subject, reference), Ok(masked.score));
+        let linear = aligner.align_in_linear_space(subject, reference).unwrap();
+        assert_eq!(linear.score, masked.score);
+        assert!(deleted_positions(&linear).iter().all(|p| !(2..10).contains(p)));
+    }
+
+    #[test]
+    fn test_masked_insertions() {
+        let reference = b"AACCGGTTAACCGGTT";
+        let subject = b"AACCGGGTTAACCGGTT";
+        let mut config = profile(reference);
+        let unmasked = GlobalProfileAligner::from(config.clone()).align(subject, reference).unwrap();
+        config.mask(2..10);
+        let masked = GlobalProfileAligner::from(config).align(subject, reference).unwrap();
+        assert_eq!(masked.score, unmasked.score);
+        assert!(masked.anchors.iter().any(|a| a.op == Op::INSERT && (2..10).contains(&a.idx.1)));
+    }
+
+    #[test]
+    fn test_no_alignment() {
+        let mut config = profile(b"ACGT");
+        config.mask(0..4);
+        let global = GlobalProfileAligner::from(config.clone());
+        assert_eq!(global.align(b"AC", b"ACGT"), Err(Error::NoAlignment));
+        assert_eq!(global.score(b"AC", b"ACGT"), Err(Error::NoAlignment));
+        assert_eq!(global.align_in_linear_space(b"AC", b"ACGT"), Err(Error::NoAlignment));
+        let banded = BandedProfileAligner { config: config.clone(), band: Band::around_main_diagonal(4) };
+        assert_eq!(banded.align(b"AC", b"ACGT").map(|banded| banded.alignment), Err(Error::NoAlignment));
+        assert_eq!(global.align(b"ACGA", b"ACGT").unwrap().score, 2.0);
+        assert_eq!(LocalProfileAligner::from(config).align(b"AC", b"ACGT").unwrap().score, 2.0);
+    }
+
+    #[test]
+    fn test_kernels_agree() {
+        let reference = b"ACGTTGCAGTCCGATGGCATTTTTACGA";
+        let subject = b"CGTTGCAAGTCCGATGCATTTTACGGA";
+        let mut config = profile(reference);
+        for (i, column) in config.columns.iter_mut().enumerate() {
+            column.scores[i % 4] += (i % 3) as FScore;
+            column.gap_penalty -= (i % 5) as FScore;
+            column.gap_extension_penalty -= 0.5 * (i % 2) as FScore;
+        }
+        config.mask(10..14);
+        let global = GlobalProfileAligner::from(config.clone());
+        let alignment = global.align(subject, reference).unwrap();
+        assert_eq!(global.score(subject, reference), Ok(alignment.score));
+        assert_eq!(global.align_in_linear_space(subject, reference).unwrap().score, alignment.score);
+        let banded = BandedProfileAligner { config: config.clone(), band: Band::around_main_diagonal(8) };
+        assert_eq!(banded.align(subject, reference).unwrap().alignment.score, alignment.score);
+        let local = LocalProfileAligner::from(config);
+        assert_eq!(local.score(subject, reference), Ok(local.align(subject, reference).unwrap().score));
+        assert_eq!(local.align_in_linear_space(subject, reference).unwrap().score, local.score(subject, reference).unwrap());
+    }
+
+    #[test]
+    fn test_reference_length() {
+        let aligner = GlobalProfileAligner::from(profile(b"ACGT"));
+        assert_eq!(aligner.align(b"ACGT", b"ACG"), Err(Error::SequenceLength { sequence: Sequence::Reference, expected: 4, found: 3 }));
+        assert_eq!(
+            aligner.score(b"ACXT", b"ACGT"),
+            Err(Error::InvalidSymbol { sequence: Sequence::Subject, position: 2, symbol: b'X' })
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = profile(b"ACGT");
+        config.columns[2].gap_penalty = 1.0;
+        assert_eq!(
+            GlobalProfileAligner::from(config).align(b"ACGT", b"ACGT"),
+            Err(Error::InvalidConfig { parameter: "profile gap penalty", value: 1.0 })
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "# two positions\nreference_gap -5 -2\n A  C\n 2 -3  -5 -2\n-3  2  -inf -inf\n";
+        let config: ProfileConfig = text.parse().unwrap();
+        assert_eq!(config.alphabet(), b"AC");
+        assert_eq!(config.score(0, b'a'), 2.0);
+        assert_eq!(config.score(1, b'A'), -3.0);
+        assert_eq!(config.column(1).gap_penalty, FORBIDDEN);
+        assert_eq!((config.reference_gap_penalty, config.reference_gap_extension_penalty), (-5.0, -2.0));
+        assert_eq!(GlobalProfileAligner::from(config).align(b"AC", b"AC").unwrap().score, 4.0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |line: usize, message: &str| Err(ProfileParseError { line, message: message.to_string() });
+        assert_eq!("".parse::<ProfileConfig>(), error(0, "no reference gap penalties"));
+        assert_eq!(" A C\n".parse::<ProfileConfig>(), error(1, "expected reference_gap"));
+        assert_eq!("reference_gap -5\n".parse::<ProfileConfig>(), error(1, "expected 2 reference gap penalties, found 1"));
+        assert_eq!("reference_gap -5 -2\n".parse::<ProfileConfig>(), error(1, "no symbol header"));
+        assert_eq!("reference_gap -5 -2\nA C\n".parse::<ProfileConfig>(), error(2, "no positions"));
+        assert_eq!("reference_gap -5 -2\nA CG\n".parse::<ProfileConfig>(), error(2, "invalid symbol CG"));
+        assert_eq!("reference_gap -5 -2\nA C\n2 -3 -5\n".parse::<ProfileConfig>(), error(3, "expected 4 values, found 3"));
+        assert_eq!("reference_gap -5 -2\nA C\n2 x -5 -2\n".parse::<ProfileConfig>(), error(3, "invalid value x"));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::config::AlignmentConfig;
+use crate::element::FScore;
+use crate::error::{Error, Result};
+
+/// Offset of the Phred qualities in FASTQ quality strings, as Sanger and Illumina 1.8+ write them.
+pub const PHRED_OFFSET: u8 = 33;
+/// The highest quality character FASTQ allows, `~`.
+const MAX_QUALITY: u8 = 126;
+
+/// The chance that a base of the given Phred quality was called wrong, `10^(-Q/10)`.
+pub fn error_probability(phred: u8) -> FScore {
+    10.0_f64.powf(-FScore::from(phred) / 10.0)
+}
+
+/// Weighs the substitution scores and reference gap penalties of `config` by the chance that
+/// the subject base they involve was called right, so a mismatch at a Q5 base costs about
+/// two thirds of one at Q40. Subject gap penalties involve no subject base and are kept.
+pub struct QualityConfig<'a, C> {
+    config: &'a C,
+    confidences: Vec<FScore>,
+    /// The first character outside the FASTQ range, failing `validate`.
+    invalid: Option<(usize, u8)>,
+}
+
+impl<'a, C> QualityConfig<'a, C> {
+    /// `qualities` as FASTQ holds them, a Phred+33 character per subject base.
+    pub fn new(config: &'a C, qualities: &[u8]) -> Self {
+        let invalid = qualities.iter()
+            .position(|&q| !(PHRED_OFFSET..=MAX_QUALITY).contains(&q))
+            .map(|position| (position, qualities[position]));
+        let confidences = qualities.iter()
+            .map(|&q| 1.0 - error_probability(q.saturating_sub(PHRED_OFFSET)))
+            .collect();
+        QualityConfig { config, confidences, invalid }
+    }
+
+    pub fn config(&self) -> &C {
+        self.config
+    }
+}
+
+impl<'a, C: AlignmentConfig<Score=FScore>> AlignmentConfig for QualityConfig<'a, C> {
+    type Score = FScore;
+
+    fn get_substitution_score(&self, pos: (usize, usize), s: u8, r: u8) -> FScore {
+        self.confidences[pos.0 - 1] * self.config.get_substitution_score(pos, s, r)
+    }
+    fn get_subject_gap_opening_penalty(&self, pos: usize) -> FScore {
+        self.config.get_subject_gap_opening_penalty(pos)
+    }
+    fn get_reference_gap_opening_penalty(&self, pos: usize) -> FScore {
+        self.confidences[pos - 1] * self.config.get_reference_gap_opening_penalty(pos)
+    }
+    fn get_subject_gap_extension_penalty(&self, pos: usize) -> FScore {
+        self.config.get_subject_gap_extension_penalty(pos)
+    }
+    fn get_reference_gap_extension_penalty(&self, pos: usize) -> FScore {
+        self.confidences[pos - 1] * self.config.get_reference_gap_extension_penalty(pos)
+    }
+    fn accepts(&self, symbol: u8) -> bool {
+        self.config.accepts(symbol)
+    }
+    fn matches(&self, s: u8, r: u8) -> bool {
+        self.config.matches(s, r)
+    }
+    fn subject_length(&self) -> Option<usize> {
+        Some(self.confidences.len())
+    }
+    fn reference_length(&self) -> Option<usize> {
+        self.config.reference_length()
+    }
+    fn validate(&self) -> Result<()> {
+        if let Some((position, quality)) = self.invalid {
+            return Err(Error::InvalidQuality { position, quality });
+        }
+        self.config.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::{Aligner, GlobalAligner, LocalAligner};
+    use crate::alignment::Alignment;
+    use crate::config::AlignmentConfig;
+    use crate::error::{Error, Sequence};
+    use crate::iupac::{IupacAlignmentConfig, NScoring};
+    use crate::nt_aligner::NtAlignmentConfig;
+    use crate::quality::{QualityConfig, error_probability};
+
+    const CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 2.0,
+        mismatch_penalty: -3.0,
+        subject_gap_penalty: -5.0,
+        subject_gap_extension_penalty: -2.0,
+        reference_gap_penalty: -5.0,
+        reference_gap_extension_penalty: -2.0,
+    };
+
+    #[test]
+    fn test_error_probability() {
+        assert_eq!(error_probability(0), 1.0);
+        assert_eq!(error_probability(10), 0.1);
+        assert!((error_probability(40) - 1e-4).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_scores() {
+        let config = QualityConfig::new(&CONFIG, b"I&!");
+        assert!((config.get_substitution_score((1, 1), b'A', b'C') + 3.0).abs() < 1e-3);
+        assert!((config.get_substitution_score((2, 1), b'A', b'C') + 3.0 * (1.0 - error_probability(5))).abs() < 1e-12);
+        assert_eq!(config.get_substitution_score((3, 1), b'A', b'A'), 0.0);
+        assert_eq!(config.get_reference_gap_opening_penalty(3), 0.0);
+        assert_eq!(config.get_subject_gap_opening_penalty(3), -5.0);
+    }
+
+    #[test]
+    fn test_matches() {
+        let iupac = IupacAlignmentConfig {
+            match_score: 2.0,
+            mismatch_penalty: -3.0,
+            n_scoring: NScoring::Ambiguous,
+            subject_gap_penalty: -5.0,
+            subject_gap_extension_penalty: -2.0,
+            reference_gap_penalty: -5.0,
+            reference_gap_extension_penalty: -2.0,
+        };
+        let aligner = GlobalAligner::from(QualityConfig::new(&iupac, b"IIII"));
+        let alignment = aligner.align(b"ACRT", b"ACGT").unwrap();
+        assert_eq!(alignment.aligned_sequences_with(&aligner.config).1, "||||");
+    }
+
+    #[test]
+    fn test_low_quality_mismatches() {
+        let subject = b"ACGTACGTTCGTACGT";
+        let reference = b"ACGTACGTACGTACGT";
+        let high = QualityConfig::new(&CONFIG, &[b'I'; 16]);
+        let mut qualities = [b'I'; 16];
+        qualities[8] = b'&';
+        let low = QualityConfig::new(&CONFIG, &qualities);
+        let high_score = GlobalAligner::from(high).align(subject, reference).unwrap().score;
+        let low_score = GlobalAligner::from(low).align(subject, reference).unwrap().score;
+        assert!(low_score > high_score);
+    }
+
+    #[test]
+    fn test_low_quality_insertion() {
+        let subject = b"ACGTTAGCACGTACGTTGCA";
+        let reference = b"ACGTTAGCCGTACGTTGCA";
+        let mut qualities = [b'I'; 20];
+        qualities[8] = b'#';
+        let aligner = LocalAligner::from(QualityConfig::new(&CONFIG, &qualities));
+        let alignment = aligner.align(subject, reference).unwrap();
+        assert_eq!(alignment.score, aligner.align_in_linear_space(subject, reference).unwrap().score);
+        assert_eq!(alignment.rescore(&aligner.config), alignment.score);
+        assert_eq!(alignment, Alignment::from("ACGTTAGCACGTACGTTGCA", "ACGTTAGC_CGTACGTTGCA", alignment.score));
+    }
+
+    #[test]
+    fn test_invalid_quality() {
+        let aligner = GlobalAligner::from(QualityConfig::new(&CONFIG, b"II\x1fI"));
+        assert_eq!(aligner.align(b"ACGT", b"ACGT"), Err(Error::InvalidQuality { position: 2, quality: 0x1f }));
+        assert!(GlobalAligner::from(QualityConfig::new(&CONFIG, b"!~II")).align(b"ACGT", b"ACGT").is_ok());
+    }
+
+    #[test]
+    fn test_length() {
+        let aligner = GlobalAligner::from(QualityConfig::new(&CONFIG, b"IIII"));
+        assert_eq!(
+            aligner.align(b"ACG", b"ACGT"),
+            Err(Error::SequenceLength { sequence: Sequence::Subject, expected: 4, found: 3 })
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use crate::alignment::{Alignment, Anchor, same_symbol};
+use crate::cigar::{basic, run_length};
+use crate::element::Op;
+use crate::iupac::reverse_complement;
+use crate::strand::{Strand, StrandedAlignment};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::iter;
+
+const UNMAPPED: u16 = 0x4;
+const REVERSE: u16 = 0x10;
+
+/// Writes alignments of reads against a single reference as SAM.
+pub struct SamWriter<W: Write> {
+    out: W,
+    reference_name: String,
+}
+
+impl<W: Write> SamWriter<W> {
+    /// Writes the header right away.
+    pub fn new(mut out: W, reference_name: &str, reference_length: usize) -> io::Result<Self> {
+        writeln!(out, "@HD\tVN:1.6\tSO:unsorted")?;
+        writeln!(out, "@SQ\tSN:{}\tLN:{}", reference_name, reference_length)?;
+        writeln!(out, "@PG\tID:seqognize\tPN:seqognize\tVN:{}", env!("CARGO_PKG_VERSION"))?;
+        Ok(SamWriter { out, reference_name: reference_name.to_string() })
+    }
+
+    /// Writes one record for `alignment` of the read `read`, with its FASTQ `quality` if any.
+    /// Gaps at the ends of the read are dropped, as SAM places the read by its first aligned
+    /// reference base instead. A read aligned to no reference base is written unmapped.
+    pub fn write(&mut self, read_name: &str, read: &[u8], quality: Option<&[u8]>, alignment: &Alignment) -> io::Result<()> {
+        self.write_record(read_name, read, quality.map(Cow::Borrowed), alignment, 0)
+    }
+
+    /// `write` for an alignment of the read on either strand; on the reverse strand the
+    /// record holds the reverse complement of the read and its qualities reversed, as SAM requires.
+    pub fn write_stranded(&mut self, read_name: &str, read: &[u8], quality: Option<&[u8]>, stranded: &StrandedAlignment) -> io::Result<()> {
+        match stranded.strand {
+            Strand::Forward => self.write_record(read_name, read, quality.map(Cow::Borrowed), &stranded.alignment, 0),
+            Strand::Reverse => {
+                let reversed = quality.map(|q| Cow::Owned(q.iter().rev().copied().collect()));
+                self.write_record(read_name, &reverse_complement(read), reversed, &stranded.alignment, REVERSE)
+            }
+        }
+    }
+
+    fn write_record(&mut self, read_name: &str, read: &[u8], quality: Option<Cow<[u8]>>, alignment: &Alignment, flag: u16) -> io::Result<()> {
+        let quality = quality.map_or_else(|| "*".to_string(), |q| String::from_utf8_lossy(&q).into_owned());
+        let pairs = aligned_pairs(alignment);
+        let first = match pairs.first() {
+            Some(anchor) if pairs.iter().any(|a| a.op == Op::MATCH) => anchor,
+            _ => return writeln!(
+                self.out,
+                "{}\t{}\t*\t0\t0\t*\t*\t0\t0\t{}\t{}",
+                read_name, UNMAPPED | (flag & REVERSE), String::from_utf8_lossy(read), quality
+            )
+        };
+        let last = pairs.last().unwrap();
+        let position = if first.op == Op::INSERT { first.idx.1 + 1 } else { first.idx.1 };
+        let cigar = run_length(
+            iter::repeat_n('S', first.idx.0 - 1)
+                .chain(pairs.iter().map(|a| basic(a.op)))
+                .chain(iter::repeat_n('S', read.len() - last.idx.0))
+        );
+        writeln!(
+            self.out,
+            "{}\t{}\t{}\t{}\t255\t{}\t*\t0\t0\t{}\t{}\tNM:i:{}\tMD:Z:{}\tAS:i:{}",
+            read_name,
+            flag,
+            self.reference_name,
+            position,
+            cigar,
+            String::from_utf8_lossy(read),
+            quality,
+            edit_distance(&pairs),
+            mismatch_string(&pairs),
+            alignment_score(alignment),
+        )
+    }
+}
+
+/// Aligned pairs in alignment order, without the gaps in the read at either end.
+fn aligned_pairs(alignment: &Alignment) -> Vec<&Anchor> {
+    let mut pairs: Vec<&Anchor> = alignment.anchors.iter()
+        .rev()
+        .skip(1)
+        .skip_while(|a| a.op == Op::DELETE)
+        .collect();
+    while pairs.last().is_some_and(|a| a.op == Op::DELETE) {
+        pairs.pop();
+    }
+    pairs
+}
+
+fn edit_distance(pairs: &[&Anchor]) -> usize {
+    pairs.iter()
+        .filter(|a| !same_symbol(a.s, a.r))
+        .count()
+}
+
+/// The MD tag: lengths of matching runs separated by mismatched and `^`-prefixed deleted
+/// reference bases.
+fn mismatch_string(pairs: &[&Anchor]) -> String {
+    let mut md = String::new();
+    let mut matching = 0;
+    let mut deleting = false;
+    for anchor in pairs {
+        match anchor.op {
+            Op::MATCH if same_symbol(anchor.s, anchor.r) => matching += 1,
+            Op::MATCH | Op::DELETE => {
+                if !(deleting && anchor.op == Op::DELETE) {
+                    md.push_str(&matching.to_string());
+                    if anchor.op == Op::DELETE {
+                        md.push('^');
+                    }
+                }
+                md.push(anchor.r as char);
+                matching = 0;
+            }
+            _ => {}
+        }
+        deleting = anchor.op == Op::DELETE;
+    }
+    md.push_str(&matching.to_string());
+    md
+}
+
+/// `AS` is an integer tag, so fractional scores are rounded.
+fn alignment_score(alignment: &Alignment) -> i64 {
+    alignment.score.round() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alignment::Alignment;
+    use crate::sam::SamWriter;
+    use crate::strand::{Strand, StrandedAlignment};
+
+    fn sam(read: &[u8], alignment: &Alignment) -> String {
+        let mut out = Vec::new();
+        let mut writer = SamWriter::new(&mut out, "chr1", 20).unwrap();
+        writer.write("read1", read, None, alignment).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_header() {
+        let mut out = Vec::new();
+        SamWriter::new(&mut out, "chr1", 20).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("@HD\tVN:1.6\tSO:unsorted\n@SQ\tSN:chr1\tLN:20\n@PG\tID:seqognize\tPN:seqognize\tVN:{}\n", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let alignment = Alignment::from("ACGTT_GCA", "ACCTTAGCA", 4.0);
+        assert_eq!(
+            sam(b"ACGTTGCA", &alignment).lines().last(),
+            Some("read1\t0\tchr1\t1\t255\t5M1D3M\t*\t0\t0\tACGTTGCA\t*\tNM:i:2\tMD:Z:2C2^A3\tAS:i:4")
+        );
+    }
+
+    #[test]
+    fn test_clipped_record() {
+        let alignment = Alignment::starting_at((2, 5), "GCA_TGG", "GCAGTAG", 2.5);
+        assert_eq!(
+            sam(b"TTGCATGGAA", &alignment).lines().last(),
+            Some("read1\t0\tchr1\t6\t255\t2S3M1D3M2S\t*\t0\t0\tTTGCATGGAA\t*\tNM:i:2\tMD:Z:3^G1A1\tAS:i:3")
+        );
+    }
+
+    #[test]
+    fn test_end_gaps_in_read() {
+        let alignment = Alignment::from("__ACGTA_", "TTACGGAC", -3.0);
+        assert_eq!(
+            sam(b"ACGTA", &alignment).lines().last(),
+            Some("read1\t0\tchr1\t3\t255\t5M\t*\t0\t0\tACGTA\t*\tNM:i:1\tMD:Z:3G1\tAS:i:-3")
+        );
+    }
+
+    #[test]
+    fn test_adjacent_mismatches_and_deletions() {
+        let alignment = Alignment::from("A__CGA", "AGTAGA", 0.0);
+        assert_eq!(
+            sam(b"ACGA", &alignment).lines().last(),
+            Some("read1\t0\tchr1\t1\t255\t1M2D3M\t*\t0\t0\tACGA\t*\tNM:i:3\tMD:Z:1^GT0A2\tAS:i:0")
+        );
+    }
+
+    #[test]
+    fn test_leading_insertion() {
+        let alignment = Alignment::starting_at((0, 3), "TACG", "_ACG", 1.0);
+        assert_eq!(
+            sam(b"TACG", &alignment).lines().last(),
+            Some("read1\t0\tchr1\t4\t255\t1I3M\t*\t0\t0\tTACG\t*\tNM:i:1\tMD:Z:3\tAS:i:1")
+        );
+    }
+
+    #[test]
+    fn test_reverse_strand() {
+        let stranded = StrandedAlignment { alignment: Alignment::from("ACGTTGCA", "ACCTTGCA", 6.0), strand: Strand::Reverse };
+        let mut out = Vec::new();
+        let mut writer = SamWriter::new(&mut out, "chr1", 20).unwrap();
+        writer.write_stranded("read1", b"TGCAACGT", Some(b"ABCDEFGH"), &stranded).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap().lines().last(),
+            Some("read1\t16\tchr1\t1\t255\t8M\t*\t0\t0\tACGTTGCA\tHGFEDCBA\tNM:i:1\tMD:Z:2C5\tAS:i:6")
+        );
+    }
+
+    #[test]
+    fn test_unmapped() {
+        assert_eq!(
+            sam(b"ACGT", &Alignment::from("", "", 0.0)).lines().last(),
+            Some("read1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\t*")
+        );
+        assert_eq!(
+            sam(b"ACGT", &Alignment::starting_at((0, 3), "ACGT", "____", -8.0)).lines().last(),
+            Some("read1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\t*")
+        );
+    }
+
+    #[test]
+    fn test_quality() {
+        let mut out = Vec::new();
+        let mut writer = SamWriter::new(&mut out, "chr1", 20).unwrap();
+        writer.write("read1", b"ACGT", Some(b"II#I"), &Alignment::from("ACGT", "ACGA", 2.0)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap().lines().last(),
+            Some("read1\t0\tchr1\t1\t255\t4M\t*\t0\t0\tACGT\tII#I\tNM:i:1\tMD:Z:3A0\tAS:i:2")
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::alignment::{Alignment, same_symbol};
+use crate::config::AlignmentConfig;
+use crate::element::{FScore, Op, Score};
+use std::fmt;
+
+/// Counts over the aligned pairs of an alignment. Insertions are gaps in the reference,
+/// deletions gaps in the subject; each run of gaps is one event.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AlignmentStats {
+    pub matches: usize,
+    pub mismatches: usize,
+    pub insertions: usize,
+    pub inserted_bases: usize,
+    pub deletions: usize,
+    pub deleted_bases: usize,
+    /// The fraction of the subject between the first and the last aligned pair.
+    pub subject_coverage: FScore,
+    /// The fraction of the reference between the first and the last aligned pair.
+    pub reference_coverage: FScore,
+}
+
+impl AlignmentStats {
+    pub fn columns(&self) -> usize {
+        self.matches + self.mismatches + self.inserted_bases + self.deleted_bases
+    }
+
+    /// Matches over all alignment columns, as BLAST reports it.
+    pub fn identity(&self) -> FScore {
+        fraction(self.matches, self.columns())
+    }
+
+    /// Matches over the columns with every gap counted once, whatever its length.
+    pub fn gap_compressed_identity(&self) -> FScore {
+        fraction(self.matches, self.matches + self.mismatches + self.insertions + self.deletions)
+    }
+}
+
+impl fmt::Display for AlignmentStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Matches: {}, Mismatches: {}, Insertions: {} ({} bp), Deletions: {} ({} bp), \
+            Identity: {:.1}%, Gap-compressed identity: {:.1}%, \
+            Subject coverage: {:.1}%, Reference coverage: {:.1}%",
+            self.matches, self.mismatches, self.insertions, self.inserted_bases, self.deletions, self.deleted_bases,
+            100.0 * self.identity(), 100.0 * self.gap_compressed_identity(),
+            100.0 * self.subject_coverage, 100.0 * self.reference_coverage,
+        )
+    }
+}
+
+impl Alignment {
+    /// Statistics of the alignment of a subject and a reference of the given lengths.
+    pub fn stats(&self, subject_length: usize, reference_length: usize) -> AlignmentStats {
+        let (start, end) = (self.start(), self.end());
+        let mut stats = AlignmentStats {
+            subject_coverage: fraction(end.0 - start.0, subject_length),
+            reference_coverage: fraction(end.1 - start.1, reference_length),
+            ..AlignmentStats::default()
+        };
+        let mut previous = Op::START;
+        for anchor in self.anchors.iter().rev().skip(1) {
+            let opens = anchor.op != previous;
+            match anchor.op {
+                Op::MATCH if same_symbol(anchor.s, anchor.r) => stats.matches += 1,
+                Op::MATCH => stats.mismatches += 1,
+                Op::INSERT => {
+                    stats.inserted_bases += 1;
+                    stats.insertions += opens as usize;
+                }
+                Op::DELETE => {
+                    stats.deleted_bases += 1;
+                    stats.deletions += opens as usize;
+                }
+                Op::START => {}
+            }
+            previous = anchor.op;
+        }
+        stats
+    }
+
+    /// The fraction of alignment columns pairing symbols that `config` scores above zero,
+    /// reported by BLAST as positives.
+    pub fn similarity<C: AlignmentConfig>(&self, config: &C) -> FScore {
+        let mut columns = 0;
+        let mut positives = 0;
+        for anchor in self.anchors.iter().rev().skip(1) {
+            columns += 1;
+            if anchor.op == Op::MATCH && config.get_substitution_score(anchor.idx, anchor.s, anchor.r) > C::Score::ZERO {
+                positives += 1;
+            }
+        }
+        fraction(positives, columns)
+    }
+}
+
+fn fraction(count: usize, total: usize) -> FScore {
+    if total == 0 {
+        0.0
+    } else {
+        count as FScore / total as FScore
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aa_aligner::AaAlignmentConfig;
+    use crate::alignment::Alignment;
+    use crate::nt_aligner::NtAlignmentConfig;
+    use crate::stats::AlignmentStats;
+
+    #[test]
+    fn test_stats() {
+        let alignment = Alignment::from("ACGT__TTA", "AGGTCCT_A", 0.0);
+        let stats = alignment.stats(7, 8);
+        assert_eq!(
+            stats,
+            AlignmentStats {
+                matches: 5,
+                mismatches: 1,
+                insertions: 1,
+                inserted_bases: 1,
+                deletions: 1,
+                deleted_bases: 2,
+                subject_coverage: 1.0,
+                reference_coverage: 1.0,
+            }
+        );
+        assert_eq!(stats.columns(), 9);
+        assert_eq!(stats.identity(), 5.0 / 9.0);
+        assert_eq!(stats.gap_compressed_identity(), 5.0 / 8.0);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let stats = Alignment::starting_at((2, 3), "GCA_TG", "GCAGTG", 4.0).stats(10, 12);
+        assert_eq!(stats.subject_coverage, 0.5);
+        assert_eq!(stats.reference_coverage, 0.5);
+        assert_eq!(stats.identity(), 5.0 / 6.0);
+        assert_eq!((stats.deletions, stats.deleted_bases), (1, 1));
+    }
+
+    #[test]
+    fn test_empty() {
+        let stats = Alignment::from("", "", 0.0).stats(0, 4);
+        assert_eq!(stats.identity(), 0.0);
+        assert_eq!(stats.gap_compressed_identity(), 0.0);
+        assert_eq!((stats.subject_coverage, stats.reference_coverage), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_similarity() {
+        let alignment = Alignment::from("HEAGAWGHE_E", "__P_AW_HEAE", 0.0);
+        assert_eq!(alignment.similarity(&AaAlignmentConfig::blosum62()), 5.0 / 11.0);
+        let nucleotides = NtAlignmentConfig {
+            match_score: 1.0,
+            mismatch_penalty: -1.0,
+            subject_gap_penalty: -1.0,
+            subject_gap_extension_penalty: -1.0,
+            reference_gap_penalty: -1.0,
+            reference_gap_extension_penalty: -1.0,
+        };
+        let alignment = Alignment::from("ACGT_A", "AGGTCA", 0.0);
+        assert_eq!(alignment.similarity(&nucleotides), alignment.stats(5, 6).identity());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Alignment::from("ACGT__TTA", "AGGTCCT_A", 0.0).stats(9, 9).to_string(),
+            "Matches: 5, Mismatches: 1, Insertions: 1 (1 bp), Deletions: 1 (2 bp), \
+            Identity: 55.6%, Gap-compressed identity: 62.5%, Subject coverage: 77.8%, Reference coverage: 88.9%"
+        );
+    }
+}
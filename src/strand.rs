@@ -0,0 +1,138 @@
+use crate::aligner::{Aligner, AlignerWorkspace};
+use crate::alignment::Alignment;
+use crate::config::AlignmentConfig;
+use crate::error::{Error, Result};
+use crate::iupac::reverse_complement;
+use std::fmt;
+
+/// Scoring of nucleotide sequences, which have a reverse strand.
+pub trait NucleotideConfig: AlignmentConfig {}
+
+/// Alignment of a nucleotide subject on whichever strand aligns better.
+pub trait BothStrands<C: NucleotideConfig>: Aligner<C> {
+    /// `align` of both the subject and its reverse complement, keeping the better alignment.
+    /// Scoring made for one subject, such as `QualityConfig`, is refused, as it would score the
+    /// reverse complement by the positions of the forward strand.
+    fn align_both_strands(&self, subject: &[u8], reference: &[u8]) -> Result<StrandedAlignment> {
+        self.align_both_strands_with(&mut AlignerWorkspace::new(), subject, reference)
+    }
+
+    fn align_both_strands_with(&self, workspace: &mut AlignerWorkspace<C::Score>, subject: &[u8], reference: &[u8]) -> Result<StrandedAlignment> {
+        if self.config().subject_length().is_some() {
+            return Err(Error::SingleStrandScoring);
+        }
+        let forward = self.align_with(workspace, subject, reference)?;
+        let reverse = self.align_with(workspace, &reverse_complement(subject), reference)?;
+        Ok(StrandedAlignment::better(forward, reverse))
+    }
+}
+
+impl<C: NucleotideConfig, A: Aligner<C>> BothStrands<C> for A {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+/// An alignment of the subject or, on the reverse strand, of its reverse complement.
+#[derive(Debug, PartialEq)]
+pub struct StrandedAlignment {
+    pub alignment: Alignment,
+    pub strand: Strand,
+}
+
+impl StrandedAlignment {
+    /// The better scoring of the alignments of a subject and of its reverse complement,
+    /// the forward one on a tie.
+    pub fn better(forward: Alignment, reverse: Alignment) -> Self {
+        if reverse.score > forward.score {
+            StrandedAlignment { alignment: reverse, strand: Strand::Reverse }
+        } else {
+            StrandedAlignment { alignment: forward, strand: Strand::Forward }
+        }
+    }
+}
+
+impl fmt::Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strand::Forward => write!(f, "+"),
+            Strand::Reverse => write!(f, "-")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner::{Aligner, AlignerWorkspace, GlobalAligner};
+    use crate::alignment::Alignment;
+    use crate::config::AlignmentConfig;
+    use crate::error::{Error, Result};
+    use crate::iupac::reverse_complement;
+    use crate::nt_aligner::{GlobalNtAligner, LocalNtAligner, NtAlignmentConfig};
+    use crate::strand::{BothStrands, NucleotideConfig, Strand, StrandedAlignment};
+
+    const CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -2.0,
+        subject_gap_penalty: -3.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -3.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    #[test]
+    fn test_reverse_strand() {
+        let reference = b"TTTTACGTTGCAAGTCCGATGCATTTTT";
+        let read = reverse_complement(b"ACGTTGCAAGTCCGATGCAT");
+        let aligner = LocalNtAligner::from(CONFIG);
+        let stranded = aligner.align_both_strands(&read, reference).unwrap();
+        assert_eq!(stranded.strand, Strand::Reverse);
+        assert_eq!(stranded.alignment, Alignment::starting_at((0, 4), "ACGTTGCAAGTCCGATGCAT", "ACGTTGCAAGTCCGATGCAT", 20.0));
+        assert!(aligner.align(&read, reference).unwrap().score < 20.0);
+    }
+
+    #[test]
+    fn test_forward_strand() {
+        let aligner = GlobalNtAligner::from(CONFIG);
+        let stranded = aligner.align_both_strands(b"ACGTTGCA", b"ACGTAGCA").unwrap();
+        assert_eq!(stranded, StrandedAlignment { alignment: Alignment::from("ACGTTGCA", "ACGTAGCA", 5.0), strand: Strand::Forward });
+    }
+
+    #[test]
+    fn test_tie() {
+        let aligner = GlobalNtAligner::from(CONFIG);
+        assert_eq!(aligner.align_both_strands(b"ACGT", b"ACGT").unwrap().strand, Strand::Forward);
+        assert!(aligner.align_both_strands(b"ACXT", b"ACGT").is_err());
+    }
+
+    struct ReadConfig(NtAlignmentConfig);
+
+    impl AlignmentConfig for ReadConfig {
+        type Score = f64;
+
+        fn get_substitution_score(&self, pos: (usize, usize), s: u8, r: u8) -> f64 {
+            self.0.get_substitution_score(pos, s, r)
+        }
+        fn get_subject_gap_opening_penalty(&self, pos: usize) -> f64 {
+            self.0.get_subject_gap_opening_penalty(pos)
+        }
+        fn get_reference_gap_opening_penalty(&self, pos: usize) -> f64 {
+            self.0.get_reference_gap_opening_penalty(pos)
+        }
+        fn subject_length(&self) -> Option<usize> {
+            Some(4)
+        }
+    }
+
+    impl NucleotideConfig for ReadConfig {}
+
+    #[test]
+    fn test_single_strand_scoring() {
+        let aligner = GlobalAligner::from(ReadConfig(CONFIG));
+        let result: Result<StrandedAlignment> = aligner.align_both_strands_with(&mut AlignerWorkspace::new(), b"ACGT", b"ACGT");
+        assert_eq!(result, Err(Error::SingleStrandScoring));
+        assert!(aligner.align(b"ACGT", b"ACGT").is_ok());
+    }
+}
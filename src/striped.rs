@@ -0,0 +1,723 @@
+use crate::aligner::{Aligner, FreeEndGaps, GlobalAligner, LocalAligner, validate};
+use crate::config::AlignmentConfig;
+use crate::element::{FScore, Score};
+use crate::error::Result;
+use std::cmp;
+
+/// Scores and penalties beyond this magnitude are left to the scalar aligners.
+const MAX_PARAMETER: i32 = 1 << 10;
+
+impl<C: AlignmentConfig> GlobalAligner<C> {
+    /// The score of `score`, computed by a striped SIMD kernel (Farrar, 2007) when the
+    /// configuration is integral and position independent and no end gaps are free.
+    /// Falls back to `score` otherwise, and when the scores outgrow 16-bit lanes.
+    pub fn striped_score(&self, subject: &[u8], reference: &[u8]) -> Result<FScore> {
+        validate(&self.config, subject, reference)?;
+        if self.free_end_gaps == FreeEndGaps::NONE {
+            if let Some(score) = striped(&self.config, subject, reference, false) {
+                return Ok(score);
+            }
+        }
+        self.score(subject, reference)
+    }
+}
+
+impl<C: AlignmentConfig> LocalAligner<C> {
+    /// The score of `score`, computed by a striped SIMD kernel in 8-bit lanes, promoted
+    /// to 16-bit lanes and then to `score` itself as the scores outgrow them.
+    pub fn striped_score(&self, subject: &[u8], reference: &[u8]) -> Result<FScore> {
+        validate(&self.config, subject, reference)?;
+        match striped(&self.config, subject, reference, true) {
+            Some(score) => Ok(score),
+            None => self.score(subject, reference)
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn striped<C: AlignmentConfig>(config: &C, subject: &[u8], reference: &[u8], local: bool) -> Option<FScore> {
+    let scoring = Scoring::of(config, subject, reference, local)?;
+    let (score, _) = x86::Isa::detect()?.score(&scoring, reference, local)?;
+    Some(FScore::from(score))
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn striped<C: AlignmentConfig>(_config: &C, _subject: &[u8], _reference: &[u8], _local: bool) -> Option<FScore> {
+    None
+}
+
+/// A configuration in integers, as the kernels use it.
+struct Scoring {
+    subject_length: usize,
+    /// Substitution scores down the subject, by reference symbol.
+    columns: Vec<Option<Vec<i32>>>,
+    lowest: i32,
+    highest: i32,
+    /// Opening and extension penalties of vertical gaps.
+    reference_gap: (i32, i32),
+    /// Opening and extension penalties of horizontal gaps.
+    subject_gap: (i32, i32),
+    top_row: Vec<i32>,
+    left_column: Vec<i32>,
+}
+
+impl Scoring {
+    fn of<C: AlignmentConfig>(config: &C, subject: &[u8], reference: &[u8], local: bool) -> Option<Self> {
+        if !config.is_position_independent() {
+            return None;
+        }
+        let reference_gap = (
+            integral(config.get_reference_gap_opening_penalty(1))?,
+            integral(config.get_reference_gap_extension_penalty(1))?,
+        );
+        let subject_gap = (
+            integral(config.get_subject_gap_opening_penalty(1))?,
+            integral(config.get_subject_gap_extension_penalty(1))?,
+        );
+        let mut columns = vec![None; 256];
+        let (mut lowest, mut highest) = (0, 0);
+        for &r in reference {
+            if columns[r as usize].is_none() {
+                let column = subject.iter()
+                    .map(|&s| integral(config.get_substitution_score((1, 1), s, r)))
+                    .collect::<Option<Vec<i32>>>()?;
+                lowest = column.iter().copied().fold(lowest, cmp::min);
+                highest = column.iter().copied().fold(highest, cmp::max);
+                columns[r as usize] = Some(column);
+            }
+        }
+        let (top_row, left_column) = if local {
+            (vec![0; reference.len() + 1], vec![0; subject.len() + 1])
+        } else {
+            (border(subject_gap, reference.len()), border(reference_gap, subject.len()))
+        };
+        Some(Scoring {
+            subject_length: subject.len(),
+            columns,
+            lowest,
+            highest,
+            reference_gap,
+            subject_gap,
+            top_row,
+            left_column,
+        })
+    }
+
+    /// How far a single move can change a score.
+    fn largest_step(&self) -> i32 {
+        [self.lowest, self.highest, self.reference_gap.0, self.reference_gap.1, self.subject_gap.0, self.subject_gap.1]
+            .iter()
+            .map(|score| score.abs())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn integral<S: Score>(score: S) -> Option<i32> {
+    let value = score.to_f64();
+    if value.fract() == 0.0 && value.abs() <= FScore::from(MAX_PARAMETER) {
+        Some(value as i32)
+    } else {
+        None
+    }
+}
+
+/// Scores of the top row or left column of a global alignment, as in `GlobalAligner`.
+fn border((opening, extension): (i32, i32), length: usize) -> Vec<i32> {
+    let mut scores: Vec<i32> = Vec::with_capacity(length + 1);
+    scores.push(0);
+    for n in 0..length {
+        let penalty = if n == 0 { opening } else { extension };
+        scores.push(scores[n].saturating_add(penalty));
+    }
+    scores
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    use crate::striped::Scoring;
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(super) enum Isa {
+        Sse2,
+        Avx2,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(super) enum Width {
+        Bits8,
+        Bits16,
+    }
+
+    impl Isa {
+        pub(super) fn detect() -> Option<Self> {
+            Self::available().pop()
+        }
+
+        /// The instruction sets this CPU supports, best last.
+        pub(super) fn available() -> Vec<Self> {
+            let mut available = Vec::new();
+            if is_x86_feature_detected!("sse2") {
+                available.push(Isa::Sse2);
+            }
+            if is_x86_feature_detected!("avx2") {
+                available.push(Isa::Avx2);
+            }
+            available
+        }
+
+        /// Local scores start out in 8-bit lanes; global ones rarely fit them and start in 16 bits.
+        /// `None` when the scores outgrow 16-bit lanes too.
+        pub(super) fn score(self, scoring: &Scoring, reference: &[u8], local: bool) -> Option<(i32, Width)> {
+            if local && fits_bytes(scoring) {
+                let score = unsafe {
+                    match self {
+                        Isa::Sse2 => sse2_u8(scoring, reference, local),
+                        Isa::Avx2 => avx2_u8(scoring, reference, local)
+                    }
+                };
+                if let Some(score) = score {
+                    return Some((score, Width::Bits8));
+                }
+            }
+            let score = unsafe {
+                match self {
+                    Isa::Sse2 => sse2_i16(scoring, reference, local),
+                    Isa::Avx2 => avx2_i16(scoring, reference, local)
+                }
+            };
+            score.map(|score| (score, Width::Bits16))
+        }
+    }
+
+    fn fits_bytes(scoring: &Scoring) -> bool {
+        let bias = (-scoring.lowest).max(0);
+        let penalties = [scoring.reference_gap.0, scoring.reference_gap.1, scoring.subject_gap.0, scoring.subject_gap.1];
+        scoring.highest.max(0) + bias < 255 && penalties.iter().all(|&penalty| penalty > -255)
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn sse2_u8(scoring: &Scoring, reference: &[u8], local: bool) -> Option<i32> {
+        kernel::<Sse2U8>(scoring, reference, local)
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn sse2_i16(scoring: &Scoring, reference: &[u8], local: bool) -> Option<i32> {
+        kernel::<Sse2I16>(scoring, reference, local)
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn avx2_u8(scoring: &Scoring, reference: &[u8], local: bool) -> Option<i32> {
+        kernel::<Avx2U8>(scoring, reference, local)
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn avx2_i16(scoring: &Scoring, reference: &[u8], local: bool) -> Option<i32> {
+        kernel::<Avx2I16>(scoring, reference, local)
+    }
+
+    /// Saturating vector arithmetic on lanes of one width. Penalties are subtracted as
+    /// magnitudes, so that unsigned lanes floor at zero. Biased lanes hold substitution
+    /// scores raised by the lowest one, to keep them unsigned.
+    trait Lanes {
+        type Vector: Copy;
+        const LANES: usize;
+        const BIASED: bool;
+        const MIN: i32;
+        const MAX: i32;
+
+        unsafe fn splat(value: i32) -> Self::Vector;
+        unsafe fn from_lanes(values: &[i32]) -> Self::Vector;
+        unsafe fn lanes(vector: Self::Vector) -> Vec<i32>;
+        unsafe fn adds(a: Self::Vector, b: Self::Vector) -> Self::Vector;
+        unsafe fn subs(a: Self::Vector, b: Self::Vector) -> Self::Vector;
+        unsafe fn max(a: Self::Vector, b: Self::Vector) -> Self::Vector;
+        unsafe fn min(a: Self::Vector, b: Self::Vector) -> Self::Vector;
+        /// Moves every lane one up, `value` entering the first.
+        unsafe fn shift_in(vector: Self::Vector, value: i32) -> Self::Vector;
+        unsafe fn any_greater(a: Self::Vector, b: Self::Vector) -> bool;
+    }
+
+    /// The striped fill of Farrar (2007), one reference column at a time. Lane `l` of the `k`-th
+    /// vector of a column holds row `l * segment + k + 1`, so that within a column only vertical
+    /// gaps cross between vectors; those crossing between lanes are added by the lazy F loop.
+    /// Returns `None` when any score may have saturated.
+    #[inline(always)]
+    unsafe fn kernel<L: Lanes>(scoring: &Scoring, reference: &[u8], local: bool) -> Option<i32> {
+        let subject_length = scoring.subject_length;
+        let segment = subject_length.div_ceil(L::LANES);
+        let bias = if L::BIASED { (-scoring.lowest).max(0) } else { 0 };
+        let margin = 2 * scoring.largest_step();
+        let (low, high) = if L::BIASED {
+            (L::MIN - 1, L::MAX - scoring.highest.max(0) - bias)
+        } else {
+            (L::MIN + margin, L::MAX - margin)
+        };
+        let in_range = |score: i32| low < score && score < high;
+        if !scoring.top_row.iter().chain(scoring.left_column.iter()).all(|&score| in_range(score)) {
+            return None;
+        }
+        let left = |row: usize| scoring.left_column[row.min(subject_length)];
+        // Extending a gap for more than opening one never pays, as it is opened from a better cell.
+        let reference_opening = L::splat(-scoring.reference_gap.0);
+        let reference_extension = L::splat(-scoring.reference_gap.1.max(scoring.reference_gap.0));
+        let subject_opening = L::splat(-scoring.subject_gap.0);
+        let subject_extension = L::splat(-scoring.subject_gap.1.max(scoring.subject_gap.0));
+        let zero = L::splat(0);
+        let bias_vector = L::splat(bias);
+
+        let mut profiles: Vec<Option<Vec<L::Vector>>> = vec![None; 256];
+        let mut h_load = stripe::<L>(segment, |row| left(row).clamp(L::MIN, L::MAX));
+        let mut h_store = vec![zero; segment];
+        let mut e_store = stripe::<L>(segment, |row| (left(row) + scoring.subject_gap.0).clamp(L::MIN, L::MAX));
+        let mut v_max = L::splat(L::MIN);
+        let mut v_min = L::splat(L::MAX);
+        for (j, &r) in reference.iter().enumerate() {
+            let profile = profiles[r as usize].get_or_insert_with(|| {
+                let column = scoring.columns[r as usize].as_ref().unwrap();
+                stripe::<L>(segment, |row| if row <= subject_length { column[row - 1] + bias } else { 0 })
+            });
+            let mut v_f = L::shift_in(
+                L::splat(L::MIN),
+                (scoring.top_row[j + 1] + scoring.reference_gap.0).clamp(L::MIN, L::MAX),
+            );
+            let mut v_h = L::shift_in(h_load[segment - 1], scoring.top_row[j]);
+            for k in 0..segment {
+                v_h = L::adds(v_h, profile[k]);
+                if L::BIASED {
+                    v_h = L::subs(v_h, bias_vector);
+                }
+                let v_e = e_store[k];
+                v_h = L::max(L::max(v_h, v_e), v_f);
+                if local {
+                    v_h = L::max(v_h, zero);
+                }
+                v_max = L::max(v_max, v_h);
+                v_min = L::min(v_min, v_h);
+                h_store[k] = v_h;
+                e_store[k] = L::max(L::subs(v_e, subject_extension), L::subs(v_h, subject_opening));
+                v_f = L::max(L::subs(v_f, reference_extension), L::subs(v_h, reference_opening));
+                v_h = h_load[k];
+            }
+            // A vertical gap carried into a row stops mattering once it neither raises the row's
+            // score nor beats opening a gap there, which the main pass has tried already.
+            'lazy: for _ in 0..L::LANES {
+                v_f = L::shift_in(v_f, L::MIN);
+                for k in 0..segment {
+                    let v_h = h_store[k];
+                    if !L::any_greater(v_f, v_h) &&
+                        !L::any_greater(L::subs(v_f, reference_extension), L::subs(v_h, reference_opening)) {
+                        break 'lazy;
+                    }
+                    let v_h = L::max(v_h, v_f);
+                    v_max = L::max(v_max, v_h);
+                    h_store[k] = v_h;
+                    e_store[k] = L::max(e_store[k], L::subs(v_h, subject_opening));
+                    v_f = L::subs(v_f, reference_extension);
+                }
+            }
+            std::mem::swap(&mut h_load, &mut h_store);
+        }
+        let highest = L::lanes(v_max).into_iter().max().unwrap();
+        let lowest = L::lanes(v_min).into_iter().min().unwrap();
+        if !in_range(highest) || !in_range(lowest) {
+            return None;
+        }
+        if local {
+            Some(highest)
+        } else {
+            let last = subject_length - 1;
+            Some(L::lanes(h_load[last % segment])[last / segment])
+        }
+    }
+
+    /// Vectors of `segment` rows each, lane `l` of the `k`-th holding the value of row `l * segment + k + 1`.
+    #[inline(always)]
+    unsafe fn stripe<L: Lanes>(segment: usize, value: impl Fn(usize) -> i32) -> Vec<L::Vector> {
+        (0..segment)
+            .map(|k| {
+                let values: Vec<i32> = (0..L::LANES).map(|lane| value(lane * segment + k + 1)).collect();
+                L::from_lanes(&values)
+            })
+            .collect()
+    }
+
+    struct Sse2U8;
+
+    impl Lanes for Sse2U8 {
+        type Vector = __m128i;
+        const LANES: usize = 16;
+        const BIASED: bool = true;
+        const MIN: i32 = 0;
+        const MAX: i32 = u8::MAX as i32;
+
+        #[inline(always)]
+        unsafe fn splat(value: i32) -> __m128i {
+            _mm_set1_epi8(value as u8 as i8)
+        }
+        #[inline(always)]
+        unsafe fn from_lanes(values: &[i32]) -> __m128i {
+            let mut lanes = [0u8; 16];
+            lanes.iter_mut().zip(values).for_each(|(lane, &value)| *lane = value as u8);
+            _mm_loadu_si128(lanes.as_ptr() as *const __m128i)
+        }
+        #[inline(always)]
+        unsafe fn lanes(vector: __m128i) -> Vec<i32> {
+            let mut lanes = [0u8; 16];
+            _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, vector);
+            lanes.iter().map(|&lane| i32::from(lane)).collect()
+        }
+        #[inline(always)]
+        unsafe fn adds(a: __m128i, b: __m128i) -> __m128i {
+            _mm_adds_epu8(a, b)
+        }
+        #[inline(always)]
+        unsafe fn subs(a: __m128i, b: __m128i) -> __m128i {
+            _mm_subs_epu8(a, b)
+        }
+        #[inline(always)]
+        unsafe fn max(a: __m128i, b: __m128i) -> __m128i {
+            _mm_max_epu8(a, b)
+        }
+        #[inline(always)]
+        unsafe fn min(a: __m128i, b: __m128i) -> __m128i {
+            _mm_min_epu8(a, b)
+        }
+        #[inline(always)]
+        unsafe fn shift_in(vector: __m128i, value: i32) -> __m128i {
+            _mm_or_si128(_mm_slli_si128::<1>(vector), _mm_cvtsi32_si128(value & 0xff))
+        }
+        #[inline(always)]
+        unsafe fn any_greater(a: __m128i, b: __m128i) -> bool {
+            _mm_movemask_epi8(_mm_cmpeq_epi8(_mm_subs_epu8(a, b), _mm_setzero_si128())) != 0xffff
+        }
+    }
+
+    struct Sse2I16;
+
+    impl Lanes for Sse2I16 {
+        type Vector = __m128i;
+        const LANES: usize = 8;
+        const BIASED: bool = false;
+        const MIN: i32 = i16::MIN as i32;
+        const MAX: i32 = i16::MAX as i32;
+
+        #[inline(always)]
+        unsafe fn splat(value: i32) -> __m128i {
+            _mm_set1_epi16(value as i16)
+        }
+        #[inline(always)]
+        unsafe fn from_lanes(values: &[i32]) -> __m128i {
+            let mut lanes = [0i16; 8];
+            lanes.iter_mut().zip(values).for_each(|(lane, &value)| *lane = value as i16);
+            _mm_loadu_si128(lanes.as_ptr() as *const __m128i)
+        }
+        #[inline(always)]
+        unsafe fn lanes(vector: __m128i) -> Vec<i32> {
+            let mut lanes = [0i16; 8];
+            _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, vector);
+            lanes.iter().map(|&lane| i32::from(lane)).collect()
+        }
+        #[inline(always)]
+        unsafe fn adds(a: __m128i, b: __m128i) -> __m128i {
+            _mm_adds_epi16(a, b)
+        }
+        #[inline(always)]
+        unsafe fn subs(a: __m128i, b: __m128i) -> __m128i {
+            _mm_subs_epi16(a, b)
+        }
+        #[inline(always)]
+        unsafe fn max(a: __m128i, b: __m128i) -> __m128i {
+            _mm_max_epi16(a, b)
+        }
+        #[inline(always)]
+        unsafe fn min(a: __m128i, b: __m128i) -> __m128i {
+            _mm_min_epi16(a, b)
+        }
+        #[inline(always)]
+        unsafe fn shift_in(vector: __m128i, value: i32) -> __m128i {
+            _mm_or_si128(_mm_slli_si128::<2>(vector), _mm_cvtsi32_si128(value & 0xffff))
+        }
+        #[inline(always)]
+        unsafe fn any_greater(a: __m128i, b: __m128i) -> bool {
+            _mm_movemask_epi8(_mm_cmpgt_epi16(a, b)) != 0
+        }
+    }
+
+    struct Avx2U8;
+
+    impl Lanes for Avx2U8 {
+        type Vector = __m256i;
+        const LANES: usize = 32;
+        const BIASED: bool = true;
+        const MIN: i32 = 0;
+        const MAX: i32 = u8::MAX as i32;
+
+        #[inline(always)]
+        unsafe fn splat(value: i32) -> __m256i {
+            _mm256_set1_epi8(value as u8 as i8)
+        }
+        #[inline(always)]
+        unsafe fn from_lanes(values: &[i32]) -> __m256i {
+            let mut lanes = [0u8; 32];
+            lanes.iter_mut().zip(values).for_each(|(lane, &value)| *lane = value as u8);
+            _mm256_loadu_si256(lanes.as_ptr() as *const __m256i)
+        }
+        #[inline(always)]
+        unsafe fn lanes(vector: __m256i) -> Vec<i32> {
+            let mut lanes = [0u8; 32];
+            _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, vector);
+            lanes.iter().map(|&lane| i32::from(lane)).collect()
+        }
+        #[inline(always)]
+        unsafe fn adds(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_adds_epu8(a, b)
+        }
+        #[inline(always)]
+        unsafe fn subs(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_subs_epu8(a, b)
+        }
+        #[inline(always)]
+        unsafe fn max(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_max_epu8(a, b)
+        }
+        #[inline(always)]
+        unsafe fn min(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_min_epu8(a, b)
+        }
+        #[inline(always)]
+        unsafe fn shift_in(vector: __m256i, value: i32) -> __m256i {
+            let carried = _mm256_permute2x128_si256::<0x08>(vector, vector);
+            let shifted = _mm256_alignr_epi8::<15>(vector, carried);
+            _mm256_or_si256(shifted, _mm256_setr_epi32(value & 0xff, 0, 0, 0, 0, 0, 0, 0))
+        }
+        #[inline(always)]
+        unsafe fn any_greater(a: __m256i, b: __m256i) -> bool {
+            _mm256_movemask_epi8(_mm256_cmpeq_epi8(_mm256_subs_epu8(a, b), _mm256_setzero_si256())) != -1
+        }
+    }
+
+    struct Avx2I16;
+
+    impl Lanes for Avx2I16 {
+        type Vector = __m256i;
+        const LANES: usize = 16;
+        const BIASED: bool = false;
+        const MIN: i32 = i16::MIN as i32;
+        const MAX: i32 = i16::MAX as i32;
+
+        #[inline(always)]
+        unsafe fn splat(value: i32) -> __m256i {
+            _mm256_set1_epi16(value as i16)
+        }
+        #[inline(always)]
+        unsafe fn from_lanes(values: &[i32]) -> __m256i {
+            let mut lanes = [0i16; 16];
+            lanes.iter_mut().zip(values).for_each(|(lane, &value)| *lane = value as i16);
+            _mm256_loadu_si256(lanes.as_ptr() as *const __m256i)
+        }
+        #[inline(always)]
+        unsafe fn lanes(vector: __m256i) -> Vec<i32> {
+            let mut lanes = [0i16; 16];
+            _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, vector);
+            lanes.iter().map(|&lane| i32::from(lane)).collect()
+        }
+        #[inline(always)]
+        unsafe fn adds(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_adds_epi16(a, b)
+        }
+        #[inline(always)]
+        unsafe fn subs(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_subs_epi16(a, b)
+        }
+        #[inline(always)]
+        unsafe fn max(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_max_epi16(a, b)
+        }
+        #[inline(always)]
+        unsafe fn min(a: __m256i, b: __m256i) -> __m256i {
+            _mm256_min_epi16(a, b)
+        }
+        #[inline(always)]
+        unsafe fn shift_in(vector: __m256i, value: i32) -> __m256i {
+            let carried = _mm256_permute2x128_si256::<0x08>(vector, vector);
+            let shifted = _mm256_alignr_epi8::<14>(vector, carried);
+            _mm256_or_si256(shifted, _mm256_setr_epi32(value & 0xffff, 0, 0, 0, 0, 0, 0, 0))
+        }
+        #[inline(always)]
+        unsafe fn any_greater(a: __m256i, b: __m256i) -> bool {
+            _mm256_movemask_epi8(_mm256_cmpgt_epi16(a, b)) != 0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aa_aligner::{AaAlignmentConfig, GlobalAaAligner, LocalAaAligner};
+    use crate::aligner::{Aligner, FreeEndGaps, LocalAligner};
+    use crate::config::AlignmentConfig;
+    use crate::nt_aligner::{GlobalNtAligner, LocalNtAligner, NtAlignmentConfig};
+    use crate::striped::Scoring;
+    use crate::substitution_matrix::SubstitutionMatrix;
+    use crate::xorshift::XorShift;
+
+    const AFFINE_CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 2.0,
+        mismatch_penalty: -3.0,
+        subject_gap_penalty: -4.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -5.0,
+        reference_gap_extension_penalty: -2.0,
+    };
+
+    const LINEAR_CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -1.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -1.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    /// Extending a gap costs more than opening one.
+    const COSTLY_EXTENSION_CONFIG: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 3.0,
+        mismatch_penalty: -2.0,
+        subject_gap_penalty: -1.0,
+        subject_gap_extension_penalty: -3.0,
+        reference_gap_penalty: -2.0,
+        reference_gap_extension_penalty: -4.0,
+    };
+
+    fn sequences(count: usize, alphabet: &[u8], max_length: u64) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut random = XorShift::new(0x9e37_79b9_7f4a_7c15);
+        let mut next = |bound: u64| random.below(bound);
+        (0..count)
+            .map(|_| {
+                let subject: Vec<u8> = (0..=next(max_length)).map(|_| alphabet[next(alphabet.len() as u64)]).collect();
+                // Every other reference is a mutated copy of the subject, to get long gapped paths.
+                let reference: Vec<u8> = if next(2) == 0 {
+                    let mut mutated = Vec::with_capacity(subject.len());
+                    for &c in subject.iter() {
+                        match next(20) {
+                            0 | 1 => {}
+                            2 => mutated.extend_from_slice(&[c, alphabet[next(alphabet.len() as u64)]]),
+                            3 => mutated.extend(subject.iter().take(next(8))),
+                            _ => mutated.push(c)
+                        }
+                    }
+                    mutated
+                } else {
+                    (0..=next(max_length)).map(|_| alphabet[next(alphabet.len() as u64)]).collect()
+                };
+                if reference.is_empty() { (subject, b"A".to_vec()) } else { (subject, reference) }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_as_scalar() {
+        let configs = [AFFINE_CONFIG, LINEAR_CONFIG, COSTLY_EXTENSION_CONFIG];
+        for (subject, reference) in sequences(300, b"ACGT", 200).iter() {
+            for config in configs.iter() {
+                let config = NtAlignmentConfig { ..*config };
+                let global = GlobalNtAligner::from(config);
+                assert_eq!(global.striped_score(subject, reference), global.score(subject, reference));
+                let local = LocalNtAligner::from(global.config);
+                assert_eq!(local.striped_score(subject, reference), local.score(subject, reference));
+            }
+        }
+    }
+
+    #[test]
+    fn test_proteins() {
+        let global = GlobalAaAligner::from(AaAlignmentConfig::blosum62());
+        let local = LocalAaAligner::from(AaAlignmentConfig::with_gaps(SubstitutionMatrix::pam30(), -9.0, -1.0));
+        for (subject, reference) in sequences(100, b"ARNDCQEGHILKMFPSTWYV", 300).iter() {
+            assert_eq!(global.striped_score(subject, reference), global.score(subject, reference));
+            assert_eq!(local.striped_score(subject, reference), local.score(subject, reference));
+        }
+    }
+
+    #[test]
+    fn test_fallback() {
+        let fractional = LocalNtAligner::from(NtAlignmentConfig { match_score: 1.5, ..AFFINE_CONFIG });
+        assert_eq!(fractional.striped_score(b"ACGTTGCA", b"ACGTGCA"), fractional.score(b"ACGTTGCA", b"ACGTGCA"));
+        let overlap = GlobalNtAligner { config: AFFINE_CONFIG, free_end_gaps: FreeEndGaps::OVERLAP };
+        assert_eq!(overlap.striped_score(b"AAAACGTT", b"CGTTGGGG"), overlap.score(b"AAAACGTT", b"CGTTGGGG"));
+        let integer = GlobalNtAligner::from(NtAlignmentConfig {
+            match_score: 2,
+            mismatch_penalty: -3,
+            subject_gap_penalty: -4,
+            subject_gap_extension_penalty: -1,
+            reference_gap_penalty: -5,
+            reference_gap_extension_penalty: -2,
+        });
+        assert_eq!(integer.striped_score(b"ACGTTGCA", b"ACGTGCA").unwrap(), 9.0);
+        assert!(integer.striped_score(b"ACGT", b"").is_err());
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[test]
+    fn test_instruction_sets() {
+        use crate::striped::x86::Isa;
+        for (subject, reference) in sequences(100, b"ACGT", 120).iter() {
+            for &local in [false, true].iter() {
+                let scoring = Scoring::of(&AFFINE_CONFIG, subject, reference, local).unwrap();
+                let expected = if local {
+                    LocalNtAligner::from(NtAlignmentConfig { ..AFFINE_CONFIG }).score(subject, reference)
+                } else {
+                    GlobalNtAligner::from(NtAlignmentConfig { ..AFFINE_CONFIG }).score(subject, reference)
+                };
+                for isa in Isa::available() {
+                    let (score, _) = isa.score(&scoring, reference, local).unwrap();
+                    assert_eq!(Ok(f64::from(score)), expected, "{:?}", isa);
+                }
+            }
+        }
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[test]
+    fn test_promotion() {
+        use crate::striped::x86::{Isa, Width};
+        let sequence = b"ACGTTGCAAGTC".repeat(20);
+        let width = |config: &NtAlignmentConfig, local: bool| {
+            let scoring = Scoring::of(config, &sequence, &sequence, local).unwrap();
+            Isa::available().iter()
+                .map(|isa| isa.score(&scoring, &sequence, local).map(|(_, width)| width))
+                .collect::<Vec<_>>()
+        };
+        let large = NtAlignmentConfig { match_score: 200.0, ..AFFINE_CONFIG };
+        assert!(width(&AFFINE_CONFIG, true).iter().all(|&width| width == Some(Width::Bits16)));
+        assert!(width(&NtAlignmentConfig { match_score: 1.0, ..AFFINE_CONFIG }, true).iter().all(|&width| width == Some(Width::Bits8)));
+        assert!(width(&large, true).iter().all(|&width| width.is_none()));
+        assert!(width(&large, false).iter().all(|&width| width.is_none()));
+        let local = LocalNtAligner::from(large);
+        assert_eq!(local.striped_score(&sequence, &sequence).unwrap(), 48000.0);
+    }
+
+    #[test]
+    fn test_position_dependent_config() {
+        struct Positional;
+        impl AlignmentConfig for Positional {
+            type Score = i32;
+            fn get_substitution_score(&self, pos: (usize, usize), s: u8, r: u8) -> i32 {
+                if s == r { 1 + (pos.0 % 3) as i32 } else { -1 }
+            }
+            fn get_subject_gap_opening_penalty(&self, _pos: usize) -> i32 {
+                -2
+            }
+            fn get_reference_gap_opening_penalty(&self, _pos: usize) -> i32 {
+                -2
+            }
+        }
+        assert!(Scoring::of(&Positional, b"ACGT", b"ACGT", true).is_none());
+        let aligner = LocalAligner::from(Positional);
+        assert_eq!(aligner.striped_score(b"ACGTACGT", b"ACGTACGT"), aligner.score(b"ACGTACGT", b"ACGTACGT"));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::element::FScore;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+const ABSENT: usize = usize::MAX;
+
+/// Residue-by-residue score table, looked up case-insensitively.
+/// Residues missing from the table score as `X` when the table has one,
+/// and as its lowest score otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubstitutionMatrix {
+    alphabet: Vec<u8>,
+    size: usize,
+    index: Vec<usize>,
+    scores: Vec<FScore>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MatrixParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MatrixParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for MatrixParseError {}
+
+impl SubstitutionMatrix {
+    pub fn blosum45() -> Self {
+        Self::builtin(include_str!("matrices/BLOSUM45"))
+    }
+
+    pub fn blosum62() -> Self {
+        Self::builtin(include_str!("matrices/BLOSUM62"))
+    }
+
+    pub fn blosum80() -> Self {
+        Self::builtin(include_str!("matrices/BLOSUM80"))
+    }
+
+    pub fn pam30() -> Self {
+        Self::builtin(include_str!("matrices/PAM30"))
+    }
+
+    pub fn pam70() -> Self {
+        Self::builtin(include_str!("matrices/PAM70"))
+    }
+
+    pub fn pam250() -> Self {
+        Self::builtin(include_str!("matrices/PAM250"))
+    }
+
+    /// Looks up one of the bundled matrices by its NCBI name, e.g. `BLOSUM62`.
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "BLOSUM45" => Some(Self::blosum45()),
+            "BLOSUM62" => Some(Self::blosum62()),
+            "BLOSUM80" => Some(Self::blosum80()),
+            "PAM30" => Some(Self::pam30()),
+            "PAM70" => Some(Self::pam70()),
+            "PAM250" => Some(Self::pam250()),
+            _ => None
+        }
+    }
+
+    /// Reads a matrix in the NCBI text format used by BLAST.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn alphabet(&self) -> &[u8] {
+        &self.alphabet
+    }
+
+    pub fn score(&self, s: u8, r: u8) -> FScore {
+        self.scores[self.index[s as usize] * self.size + self.index[r as usize]]
+    }
+
+    fn builtin(text: &str) -> Self {
+        text.parse().expect("bundled matrix is well-formed")
+    }
+
+    fn from_table(alphabet: Vec<u8>, scores: Vec<FScore>) -> Self {
+        let mut index = vec![ABSENT; 256];
+        alphabet.iter()
+            .enumerate()
+            .for_each(|(i, &residue)| {
+                index[residue.to_ascii_uppercase() as usize] = i;
+                index[residue.to_ascii_lowercase() as usize] = i;
+            });
+        let (size, scores) = match index[b'X' as usize] {
+            ABSENT => (alphabet.len() + 1, with_lowest_scoring_residue(alphabet.len(), scores)),
+            _ => (alphabet.len(), scores)
+        };
+        let fallback = index[b'X' as usize].min(size - 1);
+        index.iter_mut()
+            .filter(|i| **i == ABSENT)
+            .for_each(|i| *i = fallback);
+        SubstitutionMatrix { alphabet, size, index, scores }
+    }
+}
+
+fn with_lowest_scoring_residue(size: usize, scores: Vec<FScore>) -> Vec<FScore> {
+    let lowest = scores.iter().cloned().fold(FScore::INFINITY, FScore::min);
+    let mut extended = Vec::with_capacity((size + 1) * (size + 1));
+    for row in scores.chunks(size) {
+        extended.extend_from_slice(row);
+        extended.push(lowest);
+    }
+    extended.extend(std::iter::repeat_n(lowest, size + 1));
+    extended
+}
+
+impl FromStr for SubstitutionMatrix {
+    type Err = MatrixParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text.lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let (header_line, header) = lines.next()
+            .ok_or_else(|| parse_error(0, "no column header"))?;
+        let alphabet: Vec<u8> = header.split_whitespace()
+            .map(|column| residue(header_line, column))
+            .collect::<Result<_, _>>()?;
+        let size = alphabet.len();
+        let mut scores = vec![0.0; size * size];
+        let mut seen = vec![false; size];
+        for (n, line) in lines {
+            let mut fields = line.split_whitespace();
+            let row = residue(n, fields.next().unwrap_or_default())?;
+            let i = alphabet.iter()
+                .position(|&a| a == row)
+                .ok_or_else(|| parse_error(n, &format!("row {} has no column", row as char)))?;
+            let values: Vec<FScore> = fields
+                .map(|f| f.parse().map_err(|_| parse_error(n, &format!("invalid score {}", f))))
+                .collect::<Result<_, _>>()?;
+            if values.len() != size {
+                return Err(parse_error(n, &format!("expected {} scores, found {}", size, values.len())));
+            }
+            scores[i * size..(i + 1) * size].copy_from_slice(&values);
+            seen[i] = true;
+        }
+        match seen.iter().position(|s| !s) {
+            Some(i) => Err(parse_error(header_line, &format!("no row for {}", alphabet[i] as char))),
+            None => Ok(SubstitutionMatrix::from_table(alphabet, scores))
+        }
+    }
+}
+
+fn residue(line: usize, field: &str) -> Result<u8, MatrixParseError> {
+    match field.as_bytes() {
+        [r] => Ok(r.to_ascii_uppercase()),
+        _ => Err(parse_error(line, &format!("invalid residue {}", field)))
+    }
+}
+
+fn parse_error(line: usize, message: &str) -> MatrixParseError {
+    MatrixParseError { line, message: message.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::substitution_matrix::{SubstitutionMatrix, MatrixParseError};
+
+    const STANDARD: &[u8] = b"ARNDCQEGHILKMFPSTWYV";
+
+    #[test]
+    fn test_blosum62_scores() {
+        let blosum62 = SubstitutionMatrix::blosum62();
+        assert_eq!(blosum62.score(b'A', b'A'), 4.0);
+        assert_eq!(blosum62.score(b'W', b'W'), 11.0);
+        assert_eq!(blosum62.score(b'W', b'F'), 1.0);
+        assert_eq!(blosum62.score(b'D', b'E'), 2.0);
+        assert_eq!(blosum62.score(b'*', b'A'), -4.0);
+    }
+
+    #[test]
+    fn test_builtin_matrices_are_symmetric() {
+        let matrices = [
+            SubstitutionMatrix::blosum45(),
+            SubstitutionMatrix::blosum62(),
+            SubstitutionMatrix::blosum80(),
+            SubstitutionMatrix::pam30(),
+            SubstitutionMatrix::pam70(),
+            SubstitutionMatrix::pam250(),
+        ];
+        for matrix in matrices.iter() {
+            assert_eq!(matrix.alphabet(), b"ARNDCQEGHILKMFPSTWYVBZX*");
+            for &a in matrix.alphabet() {
+                for &b in matrix.alphabet() {
+                    assert_eq!(matrix.score(a, b), matrix.score(b, a));
+                }
+            }
+            for &a in STANDARD {
+                assert!(matrix.score(a, a) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_by_name() {
+        assert_eq!(SubstitutionMatrix::by_name("pam250"), Some(SubstitutionMatrix::pam250()));
+        assert_eq!(SubstitutionMatrix::by_name("BLOSUM100"), None);
+    }
+
+    #[test]
+    fn test_lowercase_residues() {
+        let blosum62 = SubstitutionMatrix::blosum62();
+        assert_eq!(blosum62.score(b'w', b'W'), 11.0);
+    }
+
+    #[test]
+    fn test_unknown_residue_scores_as_x() {
+        let blosum62 = SubstitutionMatrix::blosum62();
+        assert_eq!(blosum62.score(b'U', b'A'), blosum62.score(b'X', b'A'));
+    }
+
+    #[test]
+    fn test_parse() {
+        let matrix: SubstitutionMatrix = "# comment\n   A  C\nA  2 -1\nC -1  3\n".parse().unwrap();
+        assert_eq!(matrix.score(b'A', b'A'), 2.0);
+        assert_eq!(matrix.score(b'A', b'C'), -1.0);
+        assert_eq!(matrix.score(b'c', b'C'), 3.0);
+        assert_eq!(matrix.score(b'G', b'A'), -1.0);
+    }
+
+    #[test]
+    fn test_parse_row_length() {
+        assert_eq!(
+            "   A  C\nA  2\nC -1  3\n".parse::<SubstitutionMatrix>(),
+            Err(MatrixParseError { line: 2, message: "expected 2 scores, found 1".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_parse_missing_row() {
+        assert_eq!(
+            "   A  C\nA  2 -1\n".parse::<SubstitutionMatrix>(),
+            Err(MatrixParseError { line: 1, message: "no row for C".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_score() {
+        assert_eq!(
+            "   A\nA  x\n".parse::<SubstitutionMatrix>(),
+            Err(MatrixParseError { line: 2, message: "invalid score x".to_string() })
+        );
+    }
+}
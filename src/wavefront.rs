@@ -0,0 +1,429 @@
+use crate::aligner::{Aligner, FreeEndGaps, GlobalAligner, validate};
+use crate::alignment::{Alignment, AlignmentBuilder};
+use crate::config::AlignmentConfig;
+use crate::element::{FScore, Op, Score};
+use crate::error::Result;
+use std::cmp;
+use std::convert::TryFrom;
+use std::mem::size_of;
+
+/// Penalties beyond this many half units of score are left to the dynamic programming aligners.
+const MAX_PENALTY: FScore = 65536.0;
+/// The reference offset of a cell no path reaches.
+const NONE: isize = isize::MIN / 2;
+/// Wavefronts are kept for the traceback until they take this much memory, and the alignment
+/// is left to `align_in_linear_space` beyond it.
+const MAX_WAVEFRONT_BYTES: usize = 1 << 30;
+
+impl<C: AlignmentConfig> GlobalAligner<C> {
+    /// An alignment scoring as `align`, found by the wavefront algorithm (Marco-Sola et al., 2021)
+    /// in time proportional to the sequence length times the penalty of the alignment.
+    /// Needs a position independent configuration with a single match and a single mismatch
+    /// score, with all scores in half units, and no free end gaps; falls back to
+    /// `align_in_linear_space` otherwise, and for dissimilar sequences whose wavefronts
+    /// would outgrow `MAX_WAVEFRONT_BYTES`.
+    pub fn align_wavefront(&self, subject: &[u8], reference: &[u8]) -> Result<Alignment> {
+        validate(&self.config, subject, reference)?;
+        if self.free_end_gaps == FreeEndGaps::NONE {
+            if let Some(penalties) = Penalties::of(&self.config, subject, reference) {
+                if let Some(alignment) = align(&penalties, subject, reference, MAX_WAVEFRONT_BYTES)? {
+                    return Ok(alignment);
+                }
+            }
+        }
+        self.align_in_linear_space(subject, reference)
+    }
+}
+
+/// The configuration as positive costs in half units of score, with the match score taken
+/// off every aligned symbol so that matches are free (Eizenga and Paten, 2022). An alignment
+/// costing `c` then scores `(match_score * (subject_length + reference_length) - c) / 2`.
+struct Penalties {
+    match_score: FScore,
+    mismatch: usize,
+    /// Opening and extension costs of vertical gaps.
+    insertion: (usize, usize),
+    /// Opening and extension costs of horizontal gaps.
+    deletion: (usize, usize),
+}
+
+impl Penalties {
+    fn of<C: AlignmentConfig>(config: &C, subject: &[u8], reference: &[u8]) -> Option<Self> {
+        if !config.is_position_independent() {
+            return None;
+        }
+        let mut present = [false; 256];
+        subject.iter().chain(reference).for_each(|&symbol| present[symbol as usize] = true);
+        let symbols: Vec<u8> = (0..=u8::MAX).filter(|&symbol| present[symbol as usize]).collect();
+        let substitution = |s: u8, r: u8| config.get_substitution_score((1, 1), s, r).to_f64();
+        let match_score = substitution(symbols[0], symbols[0]);
+        let mut mismatch_score = None;
+        for &s in symbols.iter() {
+            for &r in symbols.iter() {
+                let score = substitution(s, r);
+                let expected = if s == r { match_score } else { *mismatch_score.get_or_insert(score) };
+                if score != expected {
+                    return None;
+                }
+            }
+        }
+        let cost = |symbols: FScore, score: FScore| {
+            let cost = symbols * match_score - 2.0 * score;
+            if cost.fract() == 0.0 && cost > 0.0 && cost <= MAX_PENALTY {
+                Some(cost as usize)
+            } else {
+                None
+            }
+        };
+        let gap = |opening: C::Score, extension: C::Score| Some((
+            cost(1.0, opening.to_f64())?,
+            cost(1.0, extension.to_f64())?,
+        ));
+        Some(Penalties {
+            match_score,
+            // Without two distinct symbols there are no mismatches to cost anything.
+            mismatch: mismatch_score.map_or(Some(1), |score| cost(2.0, score))?,
+            insertion: gap(config.get_reference_gap_opening_penalty(1), config.get_reference_gap_extension_penalty(1))?,
+            deletion: gap(config.get_subject_gap_opening_penalty(1), config.get_subject_gap_extension_penalty(1))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Component {
+    Match,
+    Insertion,
+    Deletion,
+}
+
+/// The furthest reference offsets reached at one cost, by diagonal `reference offset - subject offset`.
+struct Wavefront {
+    lo: isize,
+    offsets: Vec<[isize; 3]>,
+}
+
+impl Wavefront {
+    fn offset(wavefront: Option<&Wavefront>, diagonal: isize, component: Component) -> isize {
+        wavefront
+            .and_then(|w| w.offsets.get(usize::try_from(diagonal - w.lo).ok()?))
+            .map_or(NONE, |offsets| offsets[component as usize])
+    }
+
+    fn hi(&self) -> isize {
+        self.lo + self.offsets.len() as isize - 1
+    }
+}
+
+struct Wavefronts<'a> {
+    penalties: &'a Penalties,
+    subject: &'a [u8],
+    reference: &'a [u8],
+    /// By cost; no path has a cost whose entry is `None`.
+    wavefronts: Vec<Option<Wavefront>>,
+}
+
+impl<'a> Wavefronts<'a> {
+    fn at(&self, cost: usize, step: usize) -> Option<&Wavefront> {
+        cost.checked_sub(step).and_then(|cost| self.wavefronts[cost].as_ref())
+    }
+
+    /// The offset of a cell when it lies inside the matrix, and `NONE` otherwise.
+    fn inside(&self, diagonal: isize, offset: isize) -> isize {
+        if offset >= 0 && offset <= self.reference.len() as isize &&
+            offset - diagonal >= 0 && offset - diagonal <= self.subject.len() as isize {
+            offset
+        } else {
+            NONE
+        }
+    }
+
+    fn extend(&self, diagonal: isize, mut offset: isize) -> isize {
+        if offset == NONE {
+            return NONE;
+        }
+        while let (Some(s), Some(r)) = (self.subject.get((offset - diagonal) as usize), self.reference.get(offset as usize)) {
+            if s != r {
+                break;
+            }
+            offset += 1;
+        }
+        offset
+    }
+
+    /// The reference offsets reaching a cell in a match or mismatch before extending along the diagonal.
+    fn substitution(&self, cost: usize, diagonal: isize) -> isize {
+        let mismatch = self.at(cost, self.penalties.mismatch);
+        self.inside(diagonal, Wavefront::offset(mismatch, diagonal, Component::Match) + 1)
+    }
+
+    fn next(&self, cost: usize) -> Option<Wavefront> {
+        let (insertion_opening, insertion_extension) = self.penalties.insertion;
+        let (deletion_opening, deletion_extension) = self.penalties.deletion;
+        let sources = [
+            self.at(cost, self.penalties.mismatch),
+            self.at(cost, insertion_opening),
+            self.at(cost, insertion_extension),
+            self.at(cost, deletion_opening),
+            self.at(cost, deletion_extension),
+        ];
+        let lo = sources.iter().flatten().map(|w| w.lo).min()? - 1;
+        let hi = sources.iter().flatten().map(|w| w.hi()).max()? + 1;
+        let offsets = (lo..=hi)
+            .map(|diagonal| {
+                let insertion = self.inside(diagonal, cmp::max(
+                    Wavefront::offset(sources[1], diagonal + 1, Component::Match),
+                    Wavefront::offset(sources[2], diagonal + 1, Component::Insertion),
+                ));
+                let deletion = self.inside(diagonal, cmp::max(
+                    Wavefront::offset(sources[3], diagonal - 1, Component::Match),
+                    Wavefront::offset(sources[4], diagonal - 1, Component::Deletion),
+                ) + 1);
+                let substitution = self.substitution(cost, diagonal);
+                let furthest = cmp::max(substitution, cmp::max(insertion, deletion));
+                [self.extend(diagonal, furthest), insertion, deletion]
+            })
+            .collect();
+        Some(Wavefront { lo, offsets })
+    }
+
+    fn trace(&self, cost: usize, builder: &mut AlignmentBuilder) -> Result<()> {
+        let (mut cost, mut diagonal, mut component) = (cost, self.reference.len() as isize - self.subject.len() as isize, Component::Match);
+        let mut offset = self.reference.len() as isize;
+        let idx = |diagonal: isize, offset: isize| ((offset - diagonal) as usize, offset as usize);
+        loop {
+            match component {
+                Component::Match => {
+                    let wavefront = self.wavefronts[cost].as_ref();
+                    let insertion = Wavefront::offset(wavefront, diagonal, Component::Insertion);
+                    let deletion = Wavefront::offset(wavefront, diagonal, Component::Deletion);
+                    let substitution = self.substitution(cost, diagonal);
+                    let start = if cost == 0 { 0 } else { cmp::max(substitution, cmp::max(insertion, deletion)) };
+                    while offset > start {
+                        builder.take(Op::MATCH, idx(diagonal, offset))?;
+                        offset -= 1;
+                    }
+                    if cost == 0 {
+                        break;
+                    } else if start == substitution {
+                        builder.take(Op::MATCH, idx(diagonal, offset))?;
+                        offset -= 1;
+                        cost -= self.penalties.mismatch;
+                    } else if start == insertion {
+                        component = Component::Insertion;
+                    } else {
+                        component = Component::Deletion;
+                    }
+                }
+                Component::Insertion => {
+                    builder.take(Op::INSERT, idx(diagonal, offset))?;
+                    let (opening, extension) = self.penalties.insertion;
+                    diagonal += 1;
+                    (cost, component) = if Wavefront::offset(self.at(cost, opening), diagonal, Component::Match) == offset {
+                        (cost - opening, Component::Match)
+                    } else {
+                        (cost - extension, Component::Insertion)
+                    };
+                }
+                Component::Deletion => {
+                    builder.take(Op::DELETE, idx(diagonal, offset))?;
+                    let (opening, extension) = self.penalties.deletion;
+                    diagonal -= 1;
+                    offset -= 1;
+                    (cost, component) = if Wavefront::offset(self.at(cost, opening), diagonal, Component::Match) == offset {
+                        (cost - opening, Component::Match)
+                    } else {
+                        (cost - extension, Component::Deletion)
+                    };
+                }
+            }
+        }
+        builder.take(Op::START, (0, 0))
+    }
+}
+
+/// `None` when the wavefronts would take more than `max_bytes`.
+fn align(penalties: &Penalties, subject: &[u8], reference: &[u8], max_bytes: usize) -> Result<Option<Alignment>> {
+    let mut wavefronts = Wavefronts { penalties, subject, reference, wavefronts: Vec::new() };
+    let first = Wavefront { lo: 0, offsets: vec![[wavefronts.extend(0, 0), NONE, NONE]] };
+    wavefronts.wavefronts.push(Some(first));
+    let end_diagonal = reference.len() as isize - subject.len() as isize;
+    let mut bytes = 0;
+    let mut cost = 0;
+    while Wavefront::offset(wavefronts.wavefronts[cost].as_ref(), end_diagonal, Component::Match) != reference.len() as isize {
+        cost += 1;
+        let next = wavefronts.next(cost);
+        bytes += size_of::<Option<Wavefront>>() + next.as_ref().map_or(0, |w| w.offsets.len() * size_of::<[isize; 3]>());
+        if bytes > max_bytes {
+            return Ok(None);
+        }
+        wavefronts.wavefronts.push(next);
+    }
+    let mut builder = AlignmentBuilder::new(subject, reference);
+    wavefronts.trace(cost, &mut builder)?;
+    let symbols = (subject.len() + reference.len()) as FScore;
+    Ok(Some(builder.build((penalties.match_score * symbols - cost as FScore) / 2.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aa_aligner::{AaAlignmentConfig, GlobalAaAligner};
+    use crate::aligner::{Aligner, FreeEndGaps, GlobalAligner};
+    use crate::alignment::Alignment;
+    use crate::error::Error;
+    use crate::nt_aligner::{GlobalNtAligner, NtAlignmentConfig};
+    use crate::wavefront::{align, Penalties};
+    use crate::xorshift::XorShift;
+
+    const AFFINE: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -2.0,
+        subject_gap_extension_penalty: -0.5,
+        reference_gap_penalty: -2.0,
+        reference_gap_extension_penalty: -0.5,
+    };
+
+    const LINEAR: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 1.0,
+        mismatch_penalty: -1.0,
+        subject_gap_penalty: -1.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -1.0,
+        reference_gap_extension_penalty: -1.0,
+    };
+
+    const ASYMMETRIC: NtAlignmentConfig = NtAlignmentConfig {
+        match_score: 2.0,
+        mismatch_penalty: -3.0,
+        subject_gap_penalty: -5.0,
+        subject_gap_extension_penalty: -1.0,
+        reference_gap_penalty: -3.0,
+        reference_gap_extension_penalty: -2.0,
+    };
+
+    fn sequences(count: usize, max_length: u64) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut random = XorShift::new(0x853c_49e6_748f_ea9b);
+        let mut next = |bound: u64| random.below(bound);
+        (0..count)
+            .map(|_| {
+                let subject: Vec<u8> = (0..=next(max_length)).map(|_| b"ACGT"[next(4)]).collect();
+                let mut reference = Vec::with_capacity(subject.len());
+                for &c in subject.iter() {
+                    match next(16) {
+                        0 => {}
+                        1 => reference.push(b"ACGT"[next(4)]),
+                        2 => reference.extend((0..=next(6)).map(|_| b"ACGT"[next(4)])),
+                        3 => {
+                            reference.push(c);
+                            reference.extend(subject.iter().take(next(5)));
+                        }
+                        _ => reference.push(c)
+                    }
+                }
+                if reference.is_empty() {
+                    reference.push(b'A');
+                }
+                (subject, reference)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_as_dp() {
+        for config in [AFFINE, LINEAR, ASYMMETRIC].iter() {
+            let aligner = GlobalAligner { config: NtAlignmentConfig { ..*config }, free_end_gaps: FreeEndGaps::NONE };
+            for (subject, reference) in sequences(200, 120).iter() {
+                let alignment = aligner.align_wavefront(subject, reference).unwrap();
+                assert_eq!(alignment.score, aligner.align(subject, reference).unwrap().score);
+                assert_eq!(alignment.rescore(&aligner.config), alignment.score);
+                assert_eq!(alignment.start(), (0, 0));
+                assert_eq!(alignment.end(), (subject.len(), reference.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_align() {
+        assert_eq!(
+            GlobalNtAligner::from(AFFINE).align_wavefront(b"ACGTTGCAAGTCCGATGCAT", b"ACGTTGCAGTCCGATGGCAT"),
+            Ok(Alignment::from("ACGTTGCAAGTCCGATG_CAT", "ACGTTGCA_GTCCGATGGCAT", 15.0))
+        );
+        assert_eq!(
+            GlobalNtAligner::from(LINEAR).align_wavefront(b"ACGT", b"ACGT"),
+            Ok(Alignment::from("ACGT", "ACGT", 4.0))
+        );
+        assert_eq!(
+            GlobalNtAligner::from(LINEAR).align_wavefront(b"AAAA", b"AA"),
+            Ok(Alignment::from("AAAA", "AA__", 0.0))
+        );
+    }
+
+    #[test]
+    fn test_long_near_identical() {
+        let mut random = XorShift::new(0xda94_2042_e4dd_58b5);
+        let mut next = |bound: u64| random.below(bound);
+        let subject: Vec<u8> = (0..20_000).map(|_| b"ACGT"[next(4)]).collect();
+        let mut reference = subject.clone();
+        for &position in [1_000, 9_000, 17_000].iter() {
+            reference[position] = if subject[position] == b'A' { b'C' } else { b'A' };
+        }
+        reference.splice(12_000..12_000, b"TTTT".iter().copied());
+        let aligner = GlobalNtAligner::from(AFFINE);
+        assert!(matches!(aligner.align(&subject, &reference), Err(Error::TooLarge { .. })));
+        let alignment = aligner.align_wavefront(&subject, &reference).unwrap();
+        assert_eq!(alignment.score, 19_997.0 - 3.0 - 2.0 - 3.0 * 0.5);
+        assert_eq!(alignment.rescore(&aligner.config), alignment.score);
+    }
+
+    #[test]
+    fn test_dissimilar() {
+        let mut random = XorShift::new(0x2545_f491_4f6c_dd1d);
+        let mut next = |bound: u64| random.below(bound);
+        let subject: Vec<u8> = (0..3_000).map(|_| b"ACGT"[next(4)]).collect();
+        let reference: Vec<u8> = (0..3_000).map(|_| b"ACGT"[next(4)]).collect();
+        let penalties = Penalties::of(&AFFINE, &subject, &reference).unwrap();
+        assert_eq!(align(&penalties, &subject, &reference, 1 << 20), Ok(None));
+        let aligner = GlobalNtAligner::from(AFFINE);
+        assert_eq!(
+            aligner.align_wavefront(&subject, &reference).unwrap().score,
+            aligner.align_in_linear_space(&subject, &reference).unwrap().score
+        );
+    }
+
+    #[test]
+    fn test_integer_config() {
+        let aligner = GlobalNtAligner::from(NtAlignmentConfig {
+            match_score: 2,
+            mismatch_penalty: -3,
+            subject_gap_penalty: -5,
+            subject_gap_extension_penalty: -1,
+            reference_gap_penalty: -3,
+            reference_gap_extension_penalty: -2,
+        });
+        for (subject, reference) in sequences(20, 60).iter() {
+            assert_eq!(
+                aligner.align_wavefront(subject, reference).unwrap().score,
+                aligner.align(subject, reference).unwrap().score
+            );
+        }
+    }
+
+    #[test]
+    fn test_fallback() {
+        let proteins = GlobalAaAligner::from(AaAlignmentConfig::blosum62());
+        assert_eq!(
+            proteins.align_wavefront(b"HEAGAWGHEE", b"PAWHEAE"),
+            proteins.align_in_linear_space(b"HEAGAWGHEE", b"PAWHEAE")
+        );
+        let free_ends = GlobalAligner { config: AFFINE, free_end_gaps: FreeEndGaps::SUBJECT_IN_REFERENCE };
+        assert_eq!(
+            free_ends.align_wavefront(b"GATTACA", b"CCGATTCACC"),
+            free_ends.align_in_linear_space(b"GATTACA", b"CCGATTCACC")
+        );
+        let fractional = GlobalNtAligner::from(NtAlignmentConfig { match_score: 1.0, mismatch_penalty: -0.3, ..LINEAR });
+        assert_eq!(
+            fractional.align_wavefront(b"ACGTTA", b"ACTTA"),
+            fractional.align_in_linear_space(b"ACGTTA", b"ACTTA")
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use crate::element::FScore;
+
+/// Xorshift generator (Marsaglia, 2003), for reproducible samples and test sequences.
+pub(crate) struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    /// `seed` must not be 0.
+    pub fn new(seed: u64) -> Self {
+        XorShift { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Below `bound`, with a slight bias towards small numbers for large bounds; for the
+    /// random sequences of tests.
+    #[cfg(test)]
+    pub fn below(&mut self, bound: u64) -> usize {
+        (self.next_u64() % bound) as usize
+    }
+
+    /// In `[0, 1)`.
+    pub fn uniform(&mut self) -> FScore {
+        (self.next_u64() >> 11) as FScore / (1u64 << 53) as FScore
+    }
+}